@@ -23,27 +23,12 @@ pub fn gen_microcode_macro(input: TokenStream) -> TokenStream {
     let variant_index = 0u8..(variants.len() as u8);
 
     let name = &ast.ident;
-    let variants_fields = variants.iter().map(|v| {
-        let len;
-        if let syn::Fields::Unnamed(unnamed_fields) = &v.fields {
-            len = unnamed_fields.unnamed.len();
-        } else {
-            len = 0 
-        }
-        (0..len)
+    let variant_field_code = variants.iter().map(|v| match &v.fields {
+        syn::Fields::Unnamed(_) => quote! { (..) },
+        syn::Fields::Named(_) => quote! { { .. } },
+        syn::Fields::Unit => quote! {},
     });
 
-    let variant_field_code = variants_fields.map(|f_i| {
-        if f_i.is_empty() {
-            quote! {}
-        } else {
-            let test = "test";
-            quote! {
-                (#(field#f_i),*)
-            }
-        }
-    });
-    
     let generated_code = quote! {
         impl GenMicrocode for #name {
             fn test() {
@@ -10,17 +10,19 @@ enum NoFieldsEnum {
     Variant5,
 }
 
+#[allow(dead_code)]
 enum TestEnum {
     V0 = 0,
     V1
 }
 
+#[allow(dead_code)]
 #[derive(gen_microcode)]
 enum FieldsEnum {
     Variant1(TestEnum),
-/*    Variant2(NoFieldsEnum, NoFieldsEnum),
+    Variant2(NoFieldsEnum, NoFieldsEnum),
     Variant4(u8),
-    Variant5(u8, i32),*/
+    Variant5(u8, i32),
 }
 
 #[test]
@@ -32,3 +34,11 @@ fn returns_zero_on_first_variant() {
 fn returns_four_on_fith_variant() {
    assert_eq!(4u8, NoFieldsEnum::Variant5.into()); 
 }
+
+#[test]
+fn ignores_fields_of_variants() {
+   assert_eq!(0u8, FieldsEnum::Variant1(TestEnum::V1).into()); 
+   assert_eq!(1u8, FieldsEnum::Variant2(NoFieldsEnum::Variant1, NoFieldsEnum::Variant2).into()); 
+   assert_eq!(2u8, FieldsEnum::Variant4(42).into()); 
+   assert_eq!(3u8, FieldsEnum::Variant5(42, -1).into()); 
+}
@@ -0,0 +1,662 @@
+use crate::microcode::Keyword;
+use crate::{Instruction, Line, Pseudo, Target};
+use std::fmt;
+use std::fmt::Write;
+
+/// Memory of one bank. A program has to fit into the banks it selects.
+const MEMORY_SIZE: usize = 256;
+
+/// Number of banks the bank select register can choose from.
+const BANKS: usize = 256;
+
+/// Bytes shown per row of the listing. Longer data continues on extra rows.
+const LISTING_BYTES: usize = 4;
+
+/// What one source line was assembled into.
+#[derive(Debug, PartialEq)]
+pub struct Placed {
+    pub bank: u8,
+    /// Address within the bank.
+    pub address: usize,
+    pub bytes: Vec<u8>,
+    /// Microcode steps of the instruction, `None` for data and empty lines.
+    pub steps: Option<usize>,
+    /// The real instructions a pseudo instruction stands for.
+    pub expansion: Vec<Keyword>,
+}
+
+/// A label and where it points to. Sorts by name.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Symbol {
+    pub name: String,
+    pub bank: u8,
+    pub address: u8,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Program {
+    /// All banks in one image, bank n starting at n * 256. Gaps between
+    /// banks are filled with zeros.
+    pub image: Vec<u8>,
+    /// One entry per source line.
+    pub lines: Vec<Placed>,
+    /// Labels sorted by name.
+    pub symbols: Vec<Symbol>,
+    /// Lines that assembled, but maybe not into what was meant.
+    pub warnings: Vec<Warning>,
+}
+
+/// A line that parsed but can't be assembled.
+#[derive(Debug, PartialEq)]
+pub struct AssemblyError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AssemblyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// A line that assembled, but maybe not into what was meant.
+#[derive(Debug, PartialEq)]
+pub struct Warning {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: warning: {}", self.line, self.message)
+    }
+}
+
+/// Assembles parsed lines in two passes: the first assigns every line its
+/// bank and address and collects the labels and the `ret` following each,
+/// the second resolves jump and memory targets and emits the bytes. Each bank
+/// continues where the last `.bank` section for it ended. A label on a
+/// `.bank` line is in the bank it selects.
+pub fn assemble(lines: &[Line<'_>]) -> Result<Program, AssemblyError> {
+    let mut symbols: Vec<Symbol> = Vec::new();
+    // Labels with the address of the operand of the first `ret` after them,
+    // where `call` stores the return address.
+    let mut returns: Vec<(String, u8)> = Vec::new();
+    let mut waiting: Vec<(&str, u8)> = Vec::new();
+    let mut positions = Vec::new();
+    let mut ends = vec![0; BANKS];
+    let mut bank = 0;
+    for (index, line) in lines.iter().enumerate() {
+        let error = |message: String| AssemblyError {
+            line: index + 1,
+            message,
+        };
+        if let Some(Instruction::Bank(selected)) = line.instruction {
+            bank = selected;
+        }
+        let address = ends[bank as usize];
+        if let Some(label) = line.label {
+            if symbols.iter().any(|symbol| symbol.name == label) {
+                return Err(error(format!("label `{}` is defined twice", label)));
+            }
+            if address >= MEMORY_SIZE {
+                return Err(error(format!(
+                    "label `{}` is past the end of bank {}",
+                    label, bank
+                )));
+            }
+            symbols.push(Symbol {
+                name: String::from(label),
+                bank,
+                address: address as u8,
+            });
+            waiting.push((label, bank));
+        }
+        if let Some(Instruction::Pseudo(Pseudo::Ret)) = line.instruction {
+            let operand = (address + 1) as u8;
+            for (label, _) in waiting.iter().filter(|(_, b)| *b == bank) {
+                returns.push((String::from(*label), operand));
+            }
+            waiting.retain(|(_, b)| *b != bank);
+        }
+        positions.push((bank, address));
+        if let Some(instruction) = &line.instruction {
+            ends[bank as usize] += size(instruction).map_err(error)?;
+            if ends[bank as usize] > MEMORY_SIZE {
+                return Err(error(format!(
+                    "bank {} does not fit into {} bytes",
+                    bank, MEMORY_SIZE
+                )));
+            }
+        }
+    }
+
+    let length = ends
+        .iter()
+        .enumerate()
+        .filter(|(_, end)| **end > 0)
+        .map(|(bank, end)| bank * MEMORY_SIZE + end)
+        .max()
+        .unwrap_or(0);
+    let mut image = vec![0; length];
+    let mut placed = Vec::new();
+    let mut warnings = Vec::new();
+    for (index, (line, (bank, address))) in lines.iter().zip(positions).enumerate() {
+        if let Some(Instruction::Ldi(_, immediate)) = &line.instruction {
+            if immediate.unsigned_only {
+                warnings.push(Warning {
+                    line: index + 1,
+                    message: format!(
+                        "{} is only valid as unsigned, as a signed byte it is {}; \
+                         write 0x{:02X} or {} to say which is meant",
+                        immediate.value,
+                        immediate.value as i8,
+                        immediate.value,
+                        immediate.value as i8
+                    ),
+                });
+            }
+        }
+        let line = match &line.instruction {
+            Some(instruction) => {
+                emit(instruction, bank, address, &symbols, &returns).map_err(|message| {
+                    AssemblyError {
+                        line: index + 1,
+                        message,
+                    }
+                })?
+            }
+            None => Placed {
+                bank,
+                address,
+                bytes: Vec::new(),
+                steps: None,
+                expansion: Vec::new(),
+            },
+        };
+        let start = bank as usize * MEMORY_SIZE + address;
+        image[start..start + line.bytes.len()].copy_from_slice(&line.bytes);
+        placed.push(line);
+    }
+    symbols.sort();
+    Ok(Program {
+        image,
+        lines: placed,
+        symbols,
+        warnings,
+    })
+}
+
+fn size(instruction: &Instruction<'_>) -> Result<usize, String> {
+    match instruction {
+        Instruction::Data(bytes) => Ok(bytes.len()),
+        Instruction::Bank(_) => Ok(0),
+        Instruction::Pseudo(pseudo) => Ok(pseudo
+            .expand(0, &|_| Ok((0, 0)))?
+            .iter()
+            .map(|keyword| keyword.length() as usize)
+            .sum()),
+        _ => Ok(keyword(instruction, &|_| Ok(0))?.length() as usize),
+    }
+}
+
+/// An instruction placed at `address` in `bank`. Labels have to be in the
+/// same bank, far jumps and accesses are not supported yet.
+fn emit(
+    instruction: &Instruction<'_>,
+    bank: u8,
+    address: usize,
+    symbols: &[Symbol],
+    returns: &[(String, u8)],
+) -> Result<Placed, String> {
+    let lookup = |label: &str| match symbols.iter().find(|symbol| symbol.name == label) {
+        Some(symbol) if symbol.bank == bank => Ok(symbol.address),
+        Some(symbol) => Err(format!(
+            "label `{}` is in bank {}, but this is bank {}",
+            label, symbol.bank, bank
+        )),
+        None => Err(format!("label `{}` is not defined", label)),
+    };
+    let subroutine = |label: &str| {
+        let entry = lookup(label)?;
+        match returns.iter().find(|(name, _)| name == label) {
+            Some((_, ret)) => Ok((entry, *ret)),
+            None => Err(format!("no `ret` follows label `{}`", label)),
+        }
+    };
+    let mut placed = Placed {
+        bank,
+        address,
+        bytes: Vec::new(),
+        steps: None,
+        expansion: Vec::new(),
+    };
+    let keywords = match instruction {
+        Instruction::Data(bytes) => {
+            placed.bytes = bytes.clone();
+            return Ok(placed);
+        }
+        Instruction::Bank(_) => return Ok(placed),
+        Instruction::Pseudo(pseudo) => {
+            placed.expansion = pseudo.expand(address as u8, &subroutine)?;
+            placed.expansion.clone()
+        }
+        _ => vec![keyword(instruction, &lookup)?],
+    };
+    placed.bytes = keywords.iter().flat_map(Keyword::encode).collect();
+    placed.steps = Some(keywords.iter().map(|k| k.control_words().len()).sum());
+    Ok(placed)
+}
+
+/// The `Keyword` of a real instruction, with labels looked up.
+fn keyword(
+    instruction: &Instruction<'_>,
+    lookup: &dyn Fn(&str) -> Result<u8, String>,
+) -> Result<Keyword, String> {
+    let resolve = |target: &Target<'_>| match target {
+        Target::Address(address) => Ok(*address),
+        Target::Label(label) => lookup(label),
+    };
+    Ok(match instruction {
+        Instruction::Keyword(keyword) => keyword.clone(),
+        Instruction::Ldi(r, immediate) => Keyword::Ldi(*r, immediate.value),
+        Instruction::Jmp(target) => Keyword::Jmp(resolve(target)?),
+        Instruction::Jc(target) => Keyword::Jc(resolve(target)?),
+        Instruction::Jz(target) => Keyword::Jz(resolve(target)?),
+        Instruction::Ld(r, target) => Keyword::Ld(*r, resolve(target)?),
+        Instruction::St(target, r) => Keyword::St(resolve(target)?, *r),
+        _ => return Err(format!("{:?} is not a single instruction", instruction)),
+    })
+}
+
+/// The listing of an assembled program: every source line with its bank,
+/// address in the bank, bytes and microcode steps, followed by the symbol table with
+/// `bank:address` of every label. Pseudo instructions are followed by one row
+/// per instruction they expand to.
+pub fn listing(source: &str, program: &Program) -> String {
+    let mut listing = String::from("line  bank  addr  bytes        steps  source\n");
+    for (index, (text, placed)) in source.lines().zip(program.lines.iter()).enumerate() {
+        let pseudo = !placed.expansion.is_empty();
+        let mut rows = placed.bytes.chunks(LISTING_BYTES);
+        let first = if pseudo {
+            &[]
+        } else {
+            rows.next().unwrap_or(&[])
+        };
+        let steps = match placed.steps {
+            Some(steps) if !pseudo => steps.to_string(),
+            _ => String::new(),
+        };
+        writeln!(
+            listing,
+            "{:4}  {:>4}  {:02X}    {:<11}  {:>5}  {}",
+            index + 1,
+            placed.bank,
+            placed.address,
+            hex(first),
+            steps,
+            text
+        )
+        .unwrap();
+        if pseudo {
+            let mut address = placed.address;
+            for keyword in placed.expansion.iter() {
+                let steps = keyword.control_words().len();
+                let bytes = hex(&keyword.encode());
+                writeln!(
+                    listing,
+                    "            {:02X}    {:<11}  {:>5}    {}",
+                    address, bytes, steps, keyword
+                )
+                .unwrap();
+                address += keyword.length() as usize;
+            }
+            continue;
+        }
+        for (row, bytes) in rows.enumerate() {
+            let address = placed.address + (row + 1) * LISTING_BYTES;
+            writeln!(listing, "            {:02X}    {}", address, hex(bytes)).unwrap();
+        }
+    }
+    listing.push_str("\nsymbols\n");
+    for symbol in program.symbols.iter() {
+        writeln!(
+            listing,
+            "{}:{:02X}  {}",
+            symbol.bank, symbol.address, symbol.name
+        )
+        .unwrap();
+    }
+    listing
+}
+
+/// Debug information for simulators and debuggers as JSON: the file and line
+/// every emitted byte range comes from, and every label.
+///
+/// ```json
+/// {
+///   "lines": [
+///     {"file": "add.asm", "line": 1, "bank": 0, "address": 0, "size": 2}
+///   ],
+///   "symbols": [
+///     {"name": "start", "bank": 0, "address": 0}
+///   ]
+/// }
+/// ```
+pub fn debug_json(source_path: &str, program: &Program) -> String {
+    let file = json_string(source_path);
+    let lines: Vec<String> = program
+        .lines
+        .iter()
+        .enumerate()
+        .filter(|(_, placed)| !placed.bytes.is_empty())
+        .map(|(index, placed)| {
+            format!(
+                "    {{\"file\": {}, \"line\": {}, \"bank\": {}, \"address\": {}, \"size\": {}}}",
+                file,
+                index + 1,
+                placed.bank,
+                placed.address,
+                placed.bytes.len()
+            )
+        })
+        .collect();
+    let symbols: Vec<String> = program
+        .symbols
+        .iter()
+        .map(|symbol| {
+            format!(
+                "    {{\"name\": {}, \"bank\": {}, \"address\": {}}}",
+                json_string(&symbol.name),
+                symbol.bank,
+                symbol.address
+            )
+        })
+        .collect();
+    format!(
+        "{{\n  \"lines\": {},\n  \"symbols\": {}\n}}\n",
+        json_array(&lines),
+        json_array(&symbols)
+    )
+}
+
+fn json_array(items: &[String]) -> String {
+    if items.is_empty() {
+        String::from("[]")
+    } else {
+        format!("[\n{}\n  ]", items.join(",\n"))
+    }
+}
+
+pub fn json_string(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if (c as u32) < 0x20 => write!(quoted, "\\u{:04x}", c as u32).unwrap(),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::microcode::GPR;
+    use crate::program;
+
+    fn assemble_source(source: &str) -> Result<Program, AssemblyError> {
+        assemble(&program(source).unwrap())
+    }
+
+    #[test]
+    fn assigns_addresses_and_resolves_labels() {
+        let source = "start: ldi a, 1\nloop: add a, a\n  jmp loop\n  jmp end\nend: hlt\n";
+        let program = assemble_source(source).unwrap();
+        assert_eq!(
+            vec![0x91, 0x01, 0x34, 0x8C, 0x02, 0x8C, 0x07, 0x8F],
+            program.image
+        );
+        let addresses: Vec<usize> = program.lines.iter().map(|l| l.address).collect();
+        assert_eq!(vec![0, 2, 3, 5, 7], addresses);
+        let symbols: Vec<(&str, u8)> = program
+            .symbols
+            .iter()
+            .map(|symbol| (symbol.name.as_str(), symbol.address))
+            .collect();
+        assert_eq!(vec![("end", 7), ("loop", 2), ("start", 0)], symbols);
+    }
+
+    #[test]
+    fn counts_microcode_steps() {
+        let program = assemble_source("; nothing\nld b, [0x80]\n.ascii \"hi\"").unwrap();
+        let steps: Vec<Option<usize>> = program.lines.iter().map(|l| l.steps).collect();
+        assert_eq!(vec![None, Some(6), None], steps);
+    }
+
+    #[test]
+    fn emits_pseudo_instructions_as_their_expansion() {
+        let program = assemble_source("neg a, b\nnop").unwrap();
+        assert_eq!(vec![0x69, 0x28, 0x06, 0x90], program.image);
+        assert_eq!(Some(12), program.lines[0].steps);
+        assert_eq!(3, program.lines[1].address);
+    }
+
+    #[test]
+    fn warns_about_decimal_immediates_only_valid_as_unsigned() {
+        let program = assemble_source("ldi a, 200\nldi b, 0xC8\nldi c, -56\nldi d, 127").unwrap();
+        assert_eq!(
+            vec![0xC8; 3],
+            [program.image[1], program.image[3], program.image[5]]
+        );
+        assert_eq!(
+            vec![String::from(
+                "line 1: warning: 200 is only valid as unsigned, as a signed byte it is -56; \
+                 write 0xC8 or -56 to say which is meant"
+            )],
+            program
+                .warnings
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn places_banks_at_multiples_of_256() {
+        let source = "nop\n.bank 1\nfar: hlt\n.bank 0\nnop";
+        let program = assemble_source(source).unwrap();
+        assert_eq!(257, program.image.len());
+        assert_eq!([0x90, 0x90], program.image[..2]);
+        assert!(program.image[2..256].iter().all(|byte| *byte == 0));
+        assert_eq!(0x8F, program.image[256]);
+        let positions: Vec<(u8, usize)> =
+            program.lines.iter().map(|l| (l.bank, l.address)).collect();
+        assert_eq!(vec![(0, 0), (1, 0), (1, 0), (0, 1), (0, 1)], positions);
+        assert_eq!(
+            vec![Symbol {
+                name: String::from("far"),
+                bank: 1,
+                address: 0,
+            }],
+            program.symbols
+        );
+    }
+
+    #[test]
+    fn rejects_labels_in_other_banks() {
+        assert_eq!(
+            "line 4: label `far` is in bank 2, but this is bank 0",
+            assemble_source(".bank 2\nfar: hlt\n.bank 0\njmp far")
+                .unwrap_err()
+                .to_string()
+        );
+        assert_eq!(
+            "line 3: label `data` is in bank 0, but this is bank 1",
+            assemble_source("data: .ascii \"x\"\n.bank 1\nld a, [data]")
+                .unwrap_err()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn debug_json_maps_byte_ranges_to_lines() {
+        let program = assemble_source("start: ldi a, 1\n; loop\n.bank 1\nhlt").unwrap();
+        let expected = [
+            "{",
+            "  \"lines\": [",
+            "    {\"file\": \"src/add.asm\", \"line\": 1, \"bank\": 0, \"address\": 0, \"size\": 2},",
+            "    {\"file\": \"src/add.asm\", \"line\": 4, \"bank\": 1, \"address\": 0, \"size\": 1}",
+            "  ],",
+            "  \"symbols\": [",
+            "    {\"name\": \"start\", \"bank\": 0, \"address\": 0}",
+            "  ]",
+            "}",
+            "",
+        ]
+        .join("\n");
+        assert_eq!(expected, debug_json("src/add.asm", &program));
+    }
+
+    #[test]
+    fn debug_json_of_empty_program() {
+        let program = assemble_source("").unwrap();
+        assert_eq!(
+            "{\n  \"lines\": [],\n  \"symbols\": []\n}\n",
+            debug_json("empty.asm", &program)
+        );
+    }
+
+    #[test]
+    fn json_string_escapes_quotes_and_control_characters() {
+        assert_eq!(r#""C:\\a \"b\"\u000a""#, json_string("C:\\a \"b\"\n"));
+    }
+
+    #[test]
+    fn call_patches_the_ret_of_the_subroutine() {
+        let source = "call sub, d\nhlt\nsub: nop\nloop: ret\n";
+        let program = assemble_source(source).unwrap();
+        assert_eq!(
+            vec![0x94, 0x06, 0x9C, 0x09, 0x8C, 0x07, 0x8F, 0x90, 0x8C, 0x00],
+            program.image
+        );
+        assert_eq!(
+            vec![
+                Keyword::Ldi(GPR::D, 6),
+                Keyword::St(9, GPR::D),
+                Keyword::Jmp(7),
+            ],
+            program.lines[0].expansion
+        );
+    }
+
+    #[test]
+    fn call_needs_a_ret_after_the_label() {
+        assert_eq!(
+            "line 1: no `ret` follows label `sub`",
+            assemble_source("call sub, a\nsub: hlt")
+                .unwrap_err()
+                .to_string()
+        );
+        assert_eq!(
+            "line 1: label `sub` is in bank 1, but this is bank 0",
+            assemble_source("call sub, a\n.bank 1\nsub: ret")
+                .unwrap_err()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn listing_shows_bank_of_each_line() {
+        let source = "nop\n.bank 1\nhlt";
+        let program = assemble_source(source).unwrap();
+        let expected = [
+            "line  bank  addr  bytes        steps  source",
+            "   1     0  00    90               3  nop",
+            "   2     1  00                        .bank 1",
+            "   3     1  00    8F               4  hlt",
+            "",
+            "symbols",
+            "",
+        ]
+        .join("\n");
+        assert_eq!(expected, listing(source, &program));
+    }
+
+    #[test]
+    fn listing_shows_pseudo_instruction_expansion() {
+        let source = "neg a, b\nhlt";
+        let program = assemble_source(source).unwrap();
+        let expected = [
+            "line  bank  addr  bytes        steps  source",
+            "   1     0  00                        neg a, b",
+            "            00    69               4    xor b, b",
+            "            01    28               4    sub b, a",
+            "            02    06               4    mov a, b",
+            "   2     0  03    8F               4  hlt",
+            "",
+            "symbols",
+            "",
+        ]
+        .join("\n");
+        assert_eq!(expected, listing(source, &program));
+    }
+
+    #[test]
+    fn reports_label_errors() {
+        assert_eq!(
+            Err(AssemblyError {
+                line: 2,
+                message: String::from("label `nowhere` is not defined"),
+            }),
+            assemble_source("nop\njmp nowhere")
+        );
+        assert_eq!(
+            "line 3: label `x` is defined twice",
+            assemble_source("x: nop\nnop\nx: nop")
+                .unwrap_err()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn reports_programs_larger_than_memory() {
+        let source = "ldi a, 0\n".repeat(128) + "nop";
+        assert_eq!(
+            "line 129: bank 0 does not fit into 256 bytes",
+            assemble_source(&source).unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn listing_shows_addresses_bytes_steps_and_symbols() {
+        let source = "start: ldi a, 'A'\n\nmsg: .asciz \"hello\"\n  jmp start ; again";
+        let program = assemble_source(source).unwrap();
+        let expected = [
+            "line  bank  addr  bytes        steps  source",
+            "   1     0  00    91 41            5  start: ldi a, 'A'",
+            "   2     0  02                        ",
+            "   3     0  02    68 65 6C 6C         msg: .asciz \"hello\"",
+            "            06    6F 00",
+            "   4     0  08    8C 00            5    jmp start ; again",
+            "",
+            "symbols",
+            "0:02  msg",
+            "0:00  start",
+            "",
+        ]
+        .join("\n");
+        assert_eq!(expected, listing(source, &program));
+    }
+}
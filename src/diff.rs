@@ -0,0 +1,355 @@
+use crate::microcode::Keyword;
+use crate::output_datastructures::ControlWord;
+use std::fmt::Write;
+
+/// The microcode of one instruction as read back from a dump.
+#[derive(Debug, PartialEq)]
+pub struct Entry {
+    pub opcode: u8,
+    pub name: String,
+    pub steps: Vec<ControlWord>,
+}
+
+/// Turns generated microcode into entries that can be compared with a dump.
+/// Steps go through the EEPROM bytes, so they match what a dump reads back.
+pub fn entries(microcode: &[(Keyword, Vec<ControlWord>)]) -> Vec<Entry> {
+    microcode
+        .iter()
+        .map(|(keyword, steps)| Entry {
+            opcode: keyword.opcode(),
+            name: format!("{:?}", keyword),
+            steps: steps
+                .iter()
+                .map(|step| ControlWord::from_bytes(step.to_bytes()))
+                .collect(),
+        })
+        .collect()
+}
+
+/// Writes microcode as text, one step per line: opcode, step, the three
+/// EEPROM bytes and the instruction, e.g. `02 2 0c3800 Add(A, B)`. Keeping a
+/// dump of each revision allows comparing them with `diff` later.
+pub fn dump(microcode: &[(Keyword, Vec<ControlWord>)]) -> String {
+    let mut text = String::new();
+    for entry in entries(microcode).iter() {
+        for (index, step) in entry.steps.iter().enumerate() {
+            let [msb, middle, lsb] = step.to_bytes();
+            writeln!(
+                text,
+                "{:02x} {} {:02x}{:02x}{:02x} {}",
+                entry.opcode, index, msb, middle, lsb, entry.name
+            )
+            .unwrap();
+        }
+    }
+    text
+}
+
+/// Reads a dump written by `dump`. Steps of an instruction must be listed in
+/// order.
+pub fn parse_dump(text: &str) -> Result<Vec<Entry>, String> {
+    let mut entries: Vec<Entry> = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let error = |message: &str| format!("line {}: {}", number + 1, message);
+        if line.trim().is_empty() {
+            continue;
+        }
+        let parts: Vec<&str> = line.splitn(4, ' ').collect();
+        if parts.len() != 4 || parts[2].len() != 6 {
+            return Err(error("expected opcode, step, control word and instruction"));
+        }
+        let opcode = u8::from_str_radix(parts[0], 16).map_err(|_| error("bad opcode"))?;
+        let step: usize = parts[1].parse().map_err(|_| error("bad step"))?;
+        let word = u32::from_str_radix(parts[2], 16).map_err(|_| error("bad control word"))?;
+        let step_word =
+            ControlWord::from_bytes([(word >> 16) as u8, (word >> 8) as u8, word as u8]);
+        let name = parts[3];
+
+        let continues_last = match entries.last() {
+            Some(last) => last.name == name,
+            None => false,
+        };
+        if !continues_last {
+            entries.push(Entry {
+                opcode,
+                name: String::from(name),
+                steps: Vec::new(),
+            });
+        }
+        let entry = entries.last_mut().unwrap();
+        if entry.opcode != opcode || entry.steps.len() != step {
+            return Err(error("steps are out of order"));
+        }
+        entry.steps.push(step_word);
+    }
+    Ok(entries)
+}
+
+/// Address of a step in the microcode EEPROMs: the opcode in the high bits,
+/// the step counter in the low bits.
+pub fn rom_address(opcode: u8, step: usize, step_counter_bits: u32) -> usize {
+    (opcode as usize) << step_counter_bits | step
+}
+
+/// The images burned into the three microcode EEPROMs, most significant
+/// first. Steps an instruction doesn't use are left 0.
+pub fn rom_images(
+    microcode: &[(Keyword, Vec<ControlWord>)],
+    step_counter_bits: u32,
+) -> [Vec<u8>; 3] {
+    let size = rom_address(0xFF, 0, step_counter_bits) + (1 << step_counter_bits);
+    let mut images = [vec![0; size], vec![0; size], vec![0; size]];
+    for (keyword, steps) in microcode.iter() {
+        for (index, step) in steps.iter().enumerate() {
+            let address = rom_address(keyword.opcode(), index, step_counter_bits);
+            for (image, byte) in images.iter_mut().zip(step.to_bytes().iter()) {
+                image[address] = *byte;
+            }
+        }
+    }
+    images
+}
+
+/// Reads the microcode of every instruction back from EEPROM images written
+/// by `rom_images`. An instruction's steps end with the first one resetting
+/// the step counter, or when the counter runs out.
+pub fn read_rom_images(images: [&[u8]; 3], step_counter_bits: u32) -> Result<Vec<Entry>, String> {
+    let size = rom_address(0xFF, 0, step_counter_bits) + (1 << step_counter_bits);
+    if let Some(image) = images.iter().find(|image| image.len() != size) {
+        return Err(format!(
+            "expected images of {} bytes for a {} bit step counter, found {} bytes",
+            size,
+            step_counter_bits,
+            image.len()
+        ));
+    }
+    let entries = Keyword::all()
+        .into_iter()
+        .map(|keyword| {
+            let mut steps = Vec::new();
+            for step in 0..1 << step_counter_bits {
+                let address = rom_address(keyword.opcode(), step, step_counter_bits);
+                let word = ControlWord::from_bytes([
+                    images[0][address],
+                    images[1][address],
+                    images[2][address],
+                ]);
+                steps.push(word);
+                if steps.last().unwrap().step_reset {
+                    break;
+                }
+            }
+            Entry {
+                opcode: keyword.opcode(),
+                name: format!("{:?}", keyword),
+                steps,
+            }
+        })
+        .collect();
+    Ok(entries)
+}
+
+/// Lists every difference between two versions of the microcode, by
+/// instruction, step and ROM address, with the signals that were removed
+/// (`-`) and added (`+`).
+pub fn diff(old: &[Entry], new: &[Entry], step_counter_bits: u32) -> Vec<String> {
+    let mut changes = Vec::new();
+    for entry in old.iter() {
+        if !new.iter().any(|e| e.name == entry.name) {
+            changes.push(format!("{}: removed", title(entry)));
+        }
+    }
+    for entry in new.iter() {
+        let old_entry = match old.iter().find(|e| e.name == entry.name) {
+            Some(old_entry) => old_entry,
+            None => {
+                changes.push(format!("{}: added", title(entry)));
+                continue;
+            }
+        };
+        if old_entry.opcode != entry.opcode {
+            changes.push(format!(
+                "{}: opcode was 0x{:02X}",
+                title(entry),
+                old_entry.opcode
+            ));
+        }
+        let steps = old_entry.steps.len().max(entry.steps.len());
+        for index in 0..steps {
+            let change = match (old_entry.steps.get(index), entry.steps.get(index)) {
+                (Some(before), Some(after)) if before != after => step_change(before, after),
+                (Some(before), None) => format!("removed ({})", before.signals().join(", ")),
+                (None, Some(after)) => format!("added ({})", after.signals().join(", ")),
+                _ => continue,
+            };
+            changes.push(format!(
+                "{} step {} at 0x{:04X}: {}",
+                title(entry),
+                index,
+                rom_address(entry.opcode, index, step_counter_bits),
+                change
+            ));
+        }
+    }
+    changes
+}
+
+fn title(entry: &Entry) -> String {
+    format!("0x{:02X} {}", entry.opcode, entry.name)
+}
+
+fn step_change(before: &ControlWord, after: &ControlWord) -> String {
+    let before_signals = before.signals();
+    let after_signals = after.signals();
+    let mut changes: Vec<String> = before_signals
+        .iter()
+        .filter(|s| !after_signals.contains(s))
+        .map(|s| format!("-{}", s))
+        .collect();
+    changes.extend(
+        after_signals
+            .iter()
+            .filter(|s| !before_signals.contains(s))
+            .map(|s| format!("+{}", s)),
+    );
+    changes.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::microcode::{generate, MicrocodeConfig, GPR};
+    use crate::output_datastructures::{MEMORY, REGISTER_B};
+
+    fn entry(name: &str, steps: Vec<ControlWord>) -> Entry {
+        Entry {
+            opcode: Keyword::Nop.opcode(),
+            name: String::from(name),
+            steps,
+        }
+    }
+
+    #[test]
+    fn dump_round_trips() {
+        let microcode = generate(&MicrocodeConfig::default()).unwrap();
+        let text = dump(&microcode);
+        assert!(text.starts_with("00 0 680000 Mov(A, A)\n00 1 9e0020 Mov(A, A)\n"));
+        assert_eq!(entries(&microcode), parse_dump(&text).unwrap());
+    }
+
+    #[test]
+    fn parse_dump_reports_bad_lines() {
+        assert_eq!(
+            Err(String::from("line 2: steps are out of order")),
+            parse_dump("0c 0 680000 Hlt\n0c 2 000004 Hlt\n")
+        );
+        assert_eq!(
+            Err(String::from("line 1: bad control word")),
+            parse_dump("0c 0 6800zz Hlt\n")
+        );
+    }
+
+    #[test]
+    fn identical_microcode_has_no_changes() {
+        let microcode = generate(&MicrocodeConfig::default()).unwrap();
+        assert_eq!(
+            Vec::<String>::new(),
+            diff(&entries(&microcode), &entries(&microcode), 3)
+        );
+    }
+
+    #[test]
+    fn diff_names_changed_signals() {
+        let old = [entry("Nop", Keyword::Nop.control_words())];
+        let mut steps = Keyword::Nop.control_words();
+        steps[1].program_counter_enable = false;
+        steps[1].halt = true;
+        steps.insert(
+            2,
+            ControlWord {
+                read_from: MEMORY,
+                write_to: REGISTER_B,
+                ..ControlWord::empty()
+            },
+        );
+        let new = [entry("Nop", steps)];
+        assert_eq!(
+            vec![
+                "0x90 Nop step 1 at 0x0481: -PC+ +HLT",
+                "0x90 Nop step 2 at 0x0482: -STEP=0 +RAM→B",
+                "0x90 Nop step 3 at 0x0483: added (STEP=0)",
+            ],
+            diff(&old, &new, 3)
+        );
+    }
+
+    #[test]
+    fn diff_shows_register_moved_onto_itself() {
+        let old = [entry("Nop", vec![ControlWord::empty()])];
+        let new = [entry(
+            "Nop",
+            vec![ControlWord {
+                read_from: REGISTER_B,
+                write_to: REGISTER_B,
+                ..ControlWord::empty()
+            }],
+        )];
+        assert_eq!(vec!["0x90 Nop step 0 at 0x0480: +B→B"], diff(&old, &new, 3));
+    }
+
+    #[test]
+    fn diff_reports_added_and_removed_instructions() {
+        let old = entries(&[(Keyword::Shl(GPR::A), Keyword::Shl(GPR::A).control_words())]);
+        let new = entries(&[(Keyword::Shr(GPR::A), Keyword::Shr(GPR::A).control_words())]);
+        assert_eq!(
+            vec!["0x84 Shl(A): removed", "0x88 Shr(A): added"],
+            diff(&old, &new, 3)
+        );
+    }
+
+    #[test]
+    fn rom_images_are_addressed_by_opcode_and_step() {
+        let microcode = generate(&MicrocodeConfig::default()).unwrap();
+        let images = rom_images(&microcode, 3);
+        assert_eq!(2048, images[0].len());
+        let ldi = Keyword::Ldi(GPR::A, 0);
+        let address = 0x91 << 3 | 3;
+        assert_eq!(address, rom_address(ldi.opcode(), 3, 3));
+        let step =
+            ControlWord::from_bytes([images[0][address], images[1][address], images[2][address]]);
+        assert_eq!(ldi.control_words()[3], step);
+    }
+
+    #[test]
+    fn rom_images_round_trip() {
+        let microcode = generate(&MicrocodeConfig::default()).unwrap();
+        let [msb, middle, lsb] = rom_images(&microcode, 3);
+        assert_eq!(
+            Ok(entries(&microcode)),
+            read_rom_images([&msb, &middle, &lsb], 3)
+        );
+    }
+
+    #[test]
+    fn read_rom_images_checks_size() {
+        let image = vec![0; 1024];
+        assert_eq!(
+            Err(String::from(
+                "expected images of 2048 bytes for a 3 bit step counter, found 1024 bytes"
+            )),
+            read_rom_images([&image, &image, &image], 3)
+        );
+    }
+
+    #[test]
+    fn diff_reports_rom_addresses_of_burned_changes() {
+        let microcode = generate(&MicrocodeConfig::default()).unwrap();
+        let [mut msb, middle, lsb] = rom_images(&microcode, 3);
+        let address = rom_address(Keyword::Hlt.opcode(), 2, 3);
+        msb[address] ^= 0x10;
+        let burned = read_rom_images([&msb, &middle, &lsb], 3).unwrap();
+        let changes = diff(&burned, &entries(&microcode), 3);
+        assert_eq!(1, changes.len());
+        assert!(changes[0].starts_with("0x8F Hlt step 2 at 0x047A: "));
+    }
+}
@@ -0,0 +1,200 @@
+use field_size::FieldSize;
+use field_size_macro::FieldSize;
+
+/// How the output register is shown on the 4-digit display. On the board the
+/// mode is selected by the two highest address lines of the decoder EEPROM.
+#[derive(Copy, Clone, Debug, PartialEq, FieldSize)]
+pub enum DisplayMode {
+    Unsigned = 0,
+    Signed = 1,
+    Hex = 2,
+    Ascii = 3,
+}
+
+pub const DIGITS: usize = 4;
+
+// Segment bits as wired to the decoder EEPROM outputs, bit 7 is unused.
+const SEGMENT_A: u8 = 1 << 6;
+const SEGMENT_B: u8 = 1 << 5;
+const SEGMENT_C: u8 = 1 << 4;
+const SEGMENT_D: u8 = 1 << 3;
+const SEGMENT_E: u8 = 1 << 2;
+const SEGMENT_F: u8 = 1 << 1;
+const SEGMENT_G: u8 = 1;
+
+/// The characters shown for `value`, leftmost digit first.
+pub fn characters(value: u8, mode: DisplayMode) -> [char; DIGITS] {
+    let text = match mode {
+        DisplayMode::Unsigned => format!("{}", value),
+        DisplayMode::Signed => format!("{}", value as i8),
+        DisplayMode::Hex => format!("{:02X}", value),
+        DisplayMode::Ascii if value.is_ascii_graphic() => format!("{}", value as char),
+        DisplayMode::Ascii => String::new(),
+    };
+    let mut characters = [' '; DIGITS];
+    for (digit, c) in characters.iter_mut().rev().zip(text.chars().rev()) {
+        *digit = c;
+    }
+    characters
+}
+
+/// The segments lit for `c`. Letters use the usual 7-segment approximations
+/// regardless of case; anything that can't be shown stays dark.
+pub fn segments(c: char) -> u8 {
+    match c.to_ascii_uppercase() {
+        '0' | 'O' => SEGMENT_A | SEGMENT_B | SEGMENT_C | SEGMENT_D | SEGMENT_E | SEGMENT_F,
+        '1' => SEGMENT_B | SEGMENT_C,
+        '2' | 'Z' => SEGMENT_A | SEGMENT_B | SEGMENT_D | SEGMENT_E | SEGMENT_G,
+        '3' => SEGMENT_A | SEGMENT_B | SEGMENT_C | SEGMENT_D | SEGMENT_G,
+        '4' => SEGMENT_B | SEGMENT_C | SEGMENT_F | SEGMENT_G,
+        '5' | 'S' => SEGMENT_A | SEGMENT_C | SEGMENT_D | SEGMENT_F | SEGMENT_G,
+        '6' => SEGMENT_A | SEGMENT_C | SEGMENT_D | SEGMENT_E | SEGMENT_F | SEGMENT_G,
+        '7' => SEGMENT_A | SEGMENT_B | SEGMENT_C,
+        '8' => 0x7F,
+        '9' | 'G' => SEGMENT_A | SEGMENT_B | SEGMENT_C | SEGMENT_D | SEGMENT_F | SEGMENT_G,
+        'A' => SEGMENT_A | SEGMENT_B | SEGMENT_C | SEGMENT_E | SEGMENT_F | SEGMENT_G,
+        'B' => SEGMENT_C | SEGMENT_D | SEGMENT_E | SEGMENT_F | SEGMENT_G,
+        'C' => SEGMENT_A | SEGMENT_D | SEGMENT_E | SEGMENT_F,
+        'D' => SEGMENT_B | SEGMENT_C | SEGMENT_D | SEGMENT_E | SEGMENT_G,
+        'E' => SEGMENT_A | SEGMENT_D | SEGMENT_E | SEGMENT_F | SEGMENT_G,
+        'F' => SEGMENT_A | SEGMENT_E | SEGMENT_F | SEGMENT_G,
+        'H' | 'K' | 'X' => SEGMENT_B | SEGMENT_C | SEGMENT_E | SEGMENT_F | SEGMENT_G,
+        'I' => SEGMENT_E | SEGMENT_F,
+        'J' => SEGMENT_B | SEGMENT_C | SEGMENT_D | SEGMENT_E,
+        'L' => SEGMENT_D | SEGMENT_E | SEGMENT_F,
+        'M' | 'N' => SEGMENT_C | SEGMENT_E | SEGMENT_G,
+        'P' => SEGMENT_A | SEGMENT_B | SEGMENT_E | SEGMENT_F | SEGMENT_G,
+        'Q' => SEGMENT_A | SEGMENT_B | SEGMENT_C | SEGMENT_F | SEGMENT_G,
+        'R' => SEGMENT_E | SEGMENT_G,
+        'T' => SEGMENT_D | SEGMENT_E | SEGMENT_F | SEGMENT_G,
+        'U' | 'V' | 'W' => SEGMENT_B | SEGMENT_C | SEGMENT_D | SEGMENT_E | SEGMENT_F,
+        'Y' => SEGMENT_B | SEGMENT_C | SEGMENT_D | SEGMENT_F | SEGMENT_G,
+        '-' => SEGMENT_G,
+        '_' => SEGMENT_D,
+        '=' => SEGMENT_D | SEGMENT_G,
+        _ => 0,
+    }
+}
+
+/// Contents of the display decoder EEPROM. The address is made up of the
+/// value on A0-A7, the digit on A8-A9 (0 is the rightmost one) and the
+/// `DisplayMode` on A10-A11.
+pub fn decoder_rom() -> Vec<u8> {
+    let modes = [
+        DisplayMode::Unsigned,
+        DisplayMode::Signed,
+        DisplayMode::Hex,
+        DisplayMode::Ascii,
+    ];
+    let mut rom = vec![0; DisplayMode::field_size() * DIGITS * 256];
+    for mode in modes.iter() {
+        for value in 0..=255u8 {
+            let characters = characters(value, *mode);
+            for digit in 0..DIGITS {
+                let address = (*mode as usize) << 10 | digit << 8 | value as usize;
+                rom[address] = segments(characters[DIGITS - 1 - digit]);
+            }
+        }
+    }
+    rom
+}
+
+/// Draws `value` the way the display shows it, as three lines of text.
+pub fn render(value: u8, mode: DisplayMode) -> String {
+    let mut lines = [String::new(), String::new(), String::new()];
+    for c in characters(value, mode).iter() {
+        let lit = segments(*c);
+        let draw = |segment: u8, symbol: char| if lit & segment != 0 { symbol } else { ' ' };
+        lines[0].push(' ');
+        lines[0].push(draw(SEGMENT_A, '_'));
+        lines[0].push_str("  ");
+        lines[1].push(draw(SEGMENT_F, '|'));
+        lines[1].push(draw(SEGMENT_G, '_'));
+        lines[1].push(draw(SEGMENT_B, '|'));
+        lines[1].push(' ');
+        lines[2].push(draw(SEGMENT_E, '|'));
+        lines[2].push(draw(SEGMENT_D, '_'));
+        lines[2].push(draw(SEGMENT_C, '|'));
+        lines[2].push(' ');
+    }
+    lines
+        .iter()
+        .map(|line| line.trim_end())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unsigned_mode_shows_decimal_value() {
+        assert_eq!([' ', '2', '5', '5'], characters(255, DisplayMode::Unsigned));
+        assert_eq!([' ', ' ', ' ', '0'], characters(0, DisplayMode::Unsigned));
+    }
+
+    #[test]
+    fn signed_mode_shows_twos_complement() {
+        assert_eq!(['-', '1', '2', '8'], characters(0x80, DisplayMode::Signed));
+        assert_eq!([' ', ' ', '-', '1'], characters(0xFF, DisplayMode::Signed));
+        assert_eq!([' ', '1', '2', '7'], characters(127, DisplayMode::Signed));
+    }
+
+    #[test]
+    fn hex_mode_shows_two_digits() {
+        assert_eq!([' ', ' ', '0', 'A'], characters(10, DisplayMode::Hex));
+    }
+
+    #[test]
+    fn ascii_mode_shows_printable_characters_only() {
+        assert_eq!([' ', ' ', ' ', 'A'], characters(b'A', DisplayMode::Ascii));
+        assert_eq!([' '; DIGITS], characters(b'\n', DisplayMode::Ascii));
+        assert_eq!([' '; DIGITS], characters(b' ', DisplayMode::Ascii));
+    }
+
+    #[test]
+    fn segments_match_common_digit_patterns() {
+        assert_eq!(0x7E, segments('0'));
+        assert_eq!(0x30, segments('1'));
+        assert_eq!(0x6D, segments('2'));
+        assert_eq!(0x7B, segments('9'));
+        assert_eq!(0x01, segments('-'));
+        assert_eq!(0x00, segments(' '));
+    }
+
+    #[test]
+    fn segments_ignore_case() {
+        assert_eq!(segments('E'), segments('e'));
+    }
+
+    #[test]
+    fn decoder_rom_covers_every_mode_digit_and_value() {
+        let rom = decoder_rom();
+        assert_eq!(4096, rom.len());
+        // unsigned 123: digit 0 shows 3, digit 2 shows 1, digit 3 is blank
+        assert_eq!(segments('3'), rom[123]);
+        assert_eq!(segments('1'), rom[2 << 8 | 123]);
+        assert_eq!(0, rom[3 << 8 | 123]);
+        // signed -1: digit 1 shows the minus sign
+        assert_eq!(segments('-'), rom[1 << 10 | 1 << 8 | 0xFF]);
+        // hex 0xAB: digit 1 shows A
+        assert_eq!(segments('A'), rom[2 << 10 | 1 << 8 | 0xAB]);
+    }
+
+    #[test]
+    fn decoder_rom_tells_hex_letters_from_digits() {
+        let rom = decoder_rom();
+        let hex_digit = |value: usize| rom[2 << 10 | value];
+        let digits: Vec<u8> = (0..10).map(hex_digit).collect();
+        for letter in 10..16 {
+            assert!(!digits.contains(&hex_digit(letter)), "{:X}", letter);
+        }
+    }
+
+    #[test]
+    fn render_draws_lit_segments() {
+        let expected = ["         _", "        |_|   |", "         _|   |"].join("\n");
+        assert_eq!(expected, render(0x91, DisplayMode::Hex));
+    }
+}
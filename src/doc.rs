@@ -0,0 +1,240 @@
+use crate::microcode::Keyword;
+use crate::output_datastructures::ControlWord;
+use std::fmt::Write;
+
+/// Microcode reference as Markdown: one table per instruction and operand
+/// combination, listing the signals active in each step.
+pub fn markdown(microcode: &[(Keyword, Vec<ControlWord>)]) -> String {
+    let mut doc = String::from("# Microcode reference\n");
+    for (keyword, steps) in microcode.iter() {
+        writeln!(doc).unwrap();
+        writeln!(doc, "## {}", title(keyword)).unwrap();
+        writeln!(doc).unwrap();
+        writeln!(doc, "| Step | Signals |").unwrap();
+        writeln!(doc, "| ---: | --- |").unwrap();
+        for (index, step) in steps.iter().enumerate() {
+            writeln!(doc, "| {} | {} |", index, signals(step)).unwrap();
+        }
+    }
+    doc
+}
+
+/// The same reference as a standalone HTML page.
+pub fn html(microcode: &[(Keyword, Vec<ControlWord>)]) -> String {
+    let mut doc = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    doc.push_str("<title>Microcode reference</title>\n</head>\n<body>\n");
+    doc.push_str("<h1>Microcode reference</h1>\n");
+    for (keyword, steps) in microcode.iter() {
+        writeln!(doc, "<h2>{}</h2>", escape(&title(keyword))).unwrap();
+        doc.push_str("<table>\n<tr><th>Step</th><th>Signals</th></tr>\n");
+        for (index, step) in steps.iter().enumerate() {
+            writeln!(
+                doc,
+                "<tr><td>{}</td><td>{}</td></tr>",
+                index,
+                escape(&signals(step))
+            )
+            .unwrap();
+        }
+        doc.push_str("</table>\n");
+    }
+    doc.push_str("</body>\n</html>\n");
+    doc
+}
+
+/// Instruction set reference as Markdown: one row per instruction with its
+/// syntax, the opcodes of its register forms, its size, cycle count and
+/// whether it updates the flags, followed by a map of used and free opcodes.
+pub fn isa_markdown(microcode: &[(Keyword, Vec<ControlWord>)]) -> String {
+    let mut doc = String::from("# Instruction set\n\n");
+    doc.push_str("`r` and `r2` are A, B, C or D. `dst` is one of those, BS or OUT, ");
+    doc.push_str("`src` one of those, BS or ACC. `imm` and `addr` are the byte ");
+    doc.push_str("following the opcode. Cycles include the fetch.\n\n");
+    doc.push_str("| Instruction | Opcodes | Forms | Bytes | Cycles | Flags |\n");
+    doc.push_str("| --- | ---: | ---: | ---: | ---: | --- |\n");
+    for (keyword, opcodes, count, cycles) in forms(microcode).iter() {
+        writeln!(
+            doc,
+            "| `{}` | {} | {} | {} | {} | {} |",
+            keyword.syntax(),
+            opcodes,
+            count,
+            keyword.length(),
+            cycles,
+            if keyword.affects_flags() { "C, Z" } else { "-" }
+        )
+        .unwrap();
+    }
+    doc.push_str("\n## Opcode map\n\n");
+    doc.push_str(&opcode_map(microcode));
+    doc
+}
+
+/// The first form of every instruction, the range of opcodes its forms
+/// take, how many forms there are and the cycle count, written as a range
+/// if the operands make a difference.
+fn forms(microcode: &[(Keyword, Vec<ControlWord>)]) -> Vec<(Keyword, String, usize, String)> {
+    let mut forms: Vec<(Keyword, u8, u8, usize, usize, usize)> = Vec::new();
+    for (keyword, steps) in microcode.iter() {
+        let opcode = keyword.opcode();
+        match forms
+            .iter_mut()
+            .find(|form| form.0.syntax() == keyword.syntax())
+        {
+            Some((_, first, last, count, min, max)) => {
+                *first = (*first).min(opcode);
+                *last = (*last).max(opcode);
+                *count += 1;
+                *min = (*min).min(steps.len());
+                *max = (*max).max(steps.len());
+            }
+            None => forms.push((keyword.clone(), opcode, opcode, 1, steps.len(), steps.len())),
+        }
+    }
+    forms.sort_by_key(|form| form.1);
+    forms
+        .into_iter()
+        .map(|(keyword, first, last, count, min, max)| {
+            let opcodes = if first == last {
+                format!("0x{:02X}", first)
+            } else {
+                format!("0x{:02X}-0x{:02X}", first, last)
+            };
+            let cycles = if min == max {
+                format!("{}", min)
+            } else {
+                format!("{}-{}", min, max)
+            };
+            (keyword, opcodes, count, cycles)
+        })
+        .collect()
+}
+
+/// 16x16 grid of opcodes, high nibble down, low nibble across. Used opcodes
+/// show their mnemonic, free ones a dot.
+pub fn opcode_map(microcode: &[(Keyword, Vec<ControlWord>)]) -> String {
+    let mut cells = vec![String::from("·"); 256];
+    for (keyword, _) in microcode.iter() {
+        let mnemonic = keyword.syntax().split(' ').next().unwrap();
+        cells[keyword.opcode() as usize] = String::from(mnemonic);
+    }
+    let mut map = String::from("|    |");
+    for low in 0..16 {
+        write!(map, " x{:X} |", low).unwrap();
+    }
+    map.push_str("\n| --- |");
+    map.push_str(&" --- |".repeat(16));
+    map.push('\n');
+    for (high, row) in cells.chunks(16).enumerate() {
+        write!(map, "| {:X}x |", high).unwrap();
+        for cell in row.iter() {
+            write!(map, " {} |", cell).unwrap();
+        }
+        map.push('\n');
+    }
+    map
+}
+
+fn title(keyword: &Keyword) -> String {
+    format!("{:?} (opcode 0x{:02X})", keyword, keyword.opcode())
+}
+
+fn signals(step: &ControlWord) -> String {
+    let signals = step.signals();
+    if signals.is_empty() {
+        String::from("-")
+    } else {
+        signals.join(", ")
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::microcode::{generate, MicrocodeConfig, GPR};
+    use field_size::FieldSize;
+
+    fn ldi() -> Vec<(Keyword, Vec<ControlWord>)> {
+        let keyword = Keyword::Ldi(GPR::A, 0);
+        vec![(keyword.clone(), keyword.control_words())]
+    }
+
+    #[test]
+    fn markdown_lists_signals_per_step() {
+        let expected = [
+            "# Microcode reference",
+            "",
+            "## Ldi(A, 0) (opcode 0x91)",
+            "",
+            "| Step | Signals |",
+            "| ---: | --- |",
+            "| 0 | PC→MAR |",
+            "| 1 | RAM→IR, PC+ |",
+            "| 2 | PC→MAR |",
+            "| 3 | RAM→A, PC+ |",
+            "| 4 | STEP=0 |",
+            "",
+        ]
+        .join("\n");
+        assert_eq!(expected, markdown(&ldi()));
+    }
+
+    #[test]
+    fn html_escapes_text() {
+        let html = html(&ldi());
+        assert!(html.contains("<h2>Ldi(A, 0) (opcode 0x91)</h2>"));
+        assert!(html.contains("<tr><td>1</td><td>RAM→IR, PC+</td></tr>"));
+        assert_eq!("a &lt;b&gt; &amp; c", escape("a <b> & c"));
+    }
+
+    #[test]
+    fn isa_lists_every_opcode_once() {
+        let microcode = generate(&MicrocodeConfig::default()).unwrap();
+        let doc = isa_markdown(&microcode);
+        assert_eq!(Keyword::field_size(), doc.matches(" | 0x").count());
+        assert!(doc.contains("| `mov dst, src` | 0x00-0x23 | 36 | 1 | 4 | - |"));
+        assert!(doc.contains("| `add r, r2` | 0x34-0x43 | 16 | 1 | 4 | C, Z |"));
+        assert!(doc.contains("| `ld r, [addr]` | 0x95-0x98 | 4 | 2 | 6 | - |"));
+        assert!(doc.contains("| `hlt` | 0x8F | 1 | 1 | 4 | - |"));
+    }
+
+    #[test]
+    fn forms_show_cycle_range() {
+        let mut long = Keyword::Nop.control_words();
+        long.insert(2, ControlWord::empty());
+        let microcode = vec![
+            (Keyword::Nop, long),
+            (Keyword::Nop, Keyword::Nop.control_words()),
+        ];
+        assert_eq!(
+            vec![(Keyword::Nop, String::from("0x90"), 2, String::from("3-4"))],
+            forms(&microcode)
+        );
+    }
+
+    #[test]
+    fn opcode_map_marks_used_and_free_opcodes() {
+        let microcode = generate(&MicrocodeConfig::default()).unwrap();
+        let map = opcode_map(&microcode);
+        let lines: Vec<&str> = map.lines().collect();
+        assert_eq!(18, lines.len());
+        assert!(lines[2].starts_with("| 0x | mov | mov |"));
+        assert!(lines[4].starts_with("| 2x | mov | mov | mov | mov | sub |"));
+        assert!(lines[12].ends_with(" ld | · | · | · |"));
+        assert_eq!(256 - Keyword::all().len(), map.matches('·').count());
+    }
+
+    #[test]
+    fn markdown_covers_every_operand_combination() {
+        let microcode = generate(&MicrocodeConfig::default()).unwrap();
+        let doc = markdown(&microcode);
+        assert_eq!(microcode.len(), doc.matches("\n## ").count());
+        assert!(doc.contains("## Mov(A, B) (opcode 0x01)"));
+    }
+}
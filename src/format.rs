@@ -0,0 +1,166 @@
+use crate::{comment, directive, instruction, label_def, pseudo, LineError};
+use nom::branch::alt;
+use nom::character::complete::space0;
+use nom::combinator::{all_consuming, opt, recognize};
+use nom::sequence::tuple;
+use nom::IResult;
+
+/// Column instructions start at, unless a label is too long for it.
+const INSTRUCTION_COLUMN: usize = 8;
+
+/// The parts of a source line exactly as they are written. Formatting only
+/// moves them around, so nothing but whitespace and the case of mnemonics
+/// can change.
+#[derive(Debug, PartialEq)]
+struct Layout<'a> {
+    label: Option<&'a str>,
+    instruction: Option<&'a str>,
+    /// Including the `;` or `//`.
+    comment: Option<&'a str>,
+    indented: bool,
+}
+
+fn layout(input: &str) -> IResult<&str, Layout<'_>> {
+    let (remaining, (indent, label, _, instruction, _, comment)) = all_consuming(tuple((
+        space0,
+        opt(label_def),
+        space0,
+        opt(recognize(alt((directive, pseudo, instruction)))),
+        space0,
+        opt(recognize(comment)),
+    )))(input)?;
+    Ok((
+        remaining,
+        Layout {
+            label,
+            instruction,
+            comment,
+            indented: !indent.is_empty(),
+        },
+    ))
+}
+
+/// Lowercases the mnemonic and leaves a single space before the operands,
+/// which are kept as written.
+fn normalize(instruction: &str) -> String {
+    let end = instruction
+        .find(char::is_whitespace)
+        .unwrap_or(instruction.len());
+    let (mnemonic, operands) = instruction.split_at(end);
+    let mnemonic = mnemonic.to_ascii_lowercase();
+    if operands.is_empty() {
+        mnemonic
+    } else {
+        format!("{} {}", mnemonic, operands.trim_start())
+    }
+}
+
+fn pad(text: &mut String, column: usize) {
+    while text.len() < column {
+        text.push(' ');
+    }
+}
+
+/// Formats a source file: labels at the start of the line, instructions
+/// aligned in one column after the longest label, and comments aligned one
+/// column after the longest instruction. Lines holding only a comment stay at
+/// the start of the line unless they were indented, then they move to the
+/// instruction column. Blank lines are kept.
+pub fn format(source: &str) -> Result<String, LineError<'_>> {
+    let layouts = source
+        .lines()
+        .enumerate()
+        .map(|(number, l)| match layout(l) {
+            Ok((_, parsed)) => Ok(parsed),
+            Err(e) => Err((number + 1, e)),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let instructions: Vec<Option<String>> = layouts
+        .iter()
+        .map(|layout| layout.instruction.map(normalize))
+        .collect();
+
+    let indent = layouts
+        .iter()
+        .filter_map(|layout| layout.label)
+        .map(|label| label.len() + 2)
+        .fold(INSTRUCTION_COLUMN, usize::max);
+    let comment_column = instructions
+        .iter()
+        .flatten()
+        .map(|instruction| indent + instruction.len() + 1)
+        .max()
+        .unwrap_or(indent);
+
+    let mut formatted = String::new();
+    for (layout, instruction) in layouts.iter().zip(instructions) {
+        let mut line = String::new();
+        if let Some(label) = layout.label {
+            line.push_str(label);
+            line.push(':');
+        }
+        if let Some(instruction) = instruction {
+            pad(&mut line, indent);
+            line.push_str(&instruction);
+        }
+        if let Some(comment) = layout.comment {
+            if !line.is_empty() {
+                pad(&mut line, comment_column);
+            } else if layout.indented {
+                pad(&mut line, indent);
+            }
+            line.push_str(comment.trim_end());
+        }
+        formatted.push_str(&line);
+        formatted.push('\n');
+    }
+    Ok(formatted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aligns_labels_instructions_and_comments() {
+        let source = [
+            "; add two numbers",
+            "start:  LDI a,   'x'  ; load",
+            "  add a, B",
+            "",
+            "\t// done",
+            "the_end: hlt",
+        ]
+        .join("\n");
+        let expected = [
+            "; add two numbers",
+            "start:   ldi a,   'x' ; load",
+            "         add a, B",
+            "",
+            "         // done",
+            "the_end: hlt",
+            "",
+        ]
+        .join("\n");
+        assert_eq!(expected, format(&source).unwrap());
+    }
+
+    #[test]
+    fn keeps_strings_and_pseudo_instructions() {
+        let source = "msg: .ASCIZ \"Hi; \\\"you\\\"\"  // text\n  NEG a, b\n";
+        let expected = "msg:    .asciz \"Hi; \\\"you\\\"\" // text\n        neg a, b\n";
+        assert_eq!(expected, format(source).unwrap());
+    }
+
+    #[test]
+    fn formatting_twice_changes_nothing() {
+        let source = "x: nop ; a\nlonger_label:\n  jmp x // b\n";
+        let once = format(source).unwrap();
+        assert_eq!(once, format(&once).unwrap());
+    }
+
+    #[test]
+    fn reports_the_line_that_does_not_parse() {
+        assert_eq!(2, format("nop\nbogus\n").unwrap_err().0);
+    }
+}
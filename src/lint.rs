@@ -0,0 +1,244 @@
+use crate::microcode::{fetch_steps, Keyword};
+use crate::output_datastructures::{ControlWord, Endpoint, ENDPOINTS, FIELDS};
+use std::fmt;
+
+/// Something wrong with the register map or the microcode.
+#[derive(Debug, PartialEq)]
+pub struct Issue {
+    pub location: String,
+    pub message: String,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.location, self.message)
+    }
+}
+
+/// Checks the register map and the microcode of every instruction.
+pub fn check_all() -> Vec<Issue> {
+    let mut issues = check_register_map(&ENDPOINTS);
+    for keyword in Keyword::all() {
+        let name = format!("{:?}", keyword);
+        issues.extend(check_sequence(&name, &keyword.control_words(), &ENDPOINTS));
+    }
+    issues
+}
+
+/// Reports codes claimed by more than one endpoint. Even if one is only read
+/// and the other only written, the same constant silently means two things.
+pub fn check_register_map(endpoints: &[Endpoint]) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    for (index, endpoint) in endpoints.iter().enumerate() {
+        for other in endpoints[index + 1..].iter() {
+            if endpoint.code == other.code {
+                issues.push(Issue {
+                    location: String::from("register map"),
+                    message: format!(
+                        "{} and {} share code {}",
+                        endpoint.name, other.name, endpoint.code
+                    ),
+                });
+            }
+        }
+    }
+    issues
+}
+
+/// Checks the steps of one instruction: every field of every step, that the
+/// fetch cycle comes first and that the step counter is reset at the end.
+pub fn check_sequence(name: &str, steps: &[ControlWord], endpoints: &[Endpoint]) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    let issue = |message: String| Issue {
+        location: String::from(name),
+        message,
+    };
+    if !steps.starts_with(&fetch_steps()) {
+        issues.push(issue(String::from("does not start with the fetch cycle")));
+    }
+    for (index, step) in steps.iter().enumerate() {
+        for message in check_step(step, endpoints) {
+            issues.push(Issue {
+                location: format!("{} step {}", name, index),
+                message,
+            });
+        }
+    }
+    match steps.iter().position(|step| step.step_reset) {
+        None => issues.push(issue(String::from("never resets the step counter"))),
+        Some(index) if index + 1 < steps.len() => issues.push(issue(format!(
+            "steps after {} are unreachable because of step_reset",
+            index
+        ))),
+        _ => (),
+    }
+    issues
+}
+
+fn check_step(step: &ControlWord, endpoints: &[Endpoint]) -> Vec<String> {
+    let mut messages = Vec::new();
+    for ((field, width), value) in FIELDS.iter().zip(step.field_values().iter()) {
+        if (*value as usize) >> width != 0 {
+            messages.push(format!(
+                "{} value {} does not fit into {} bits",
+                field, value, width
+            ));
+        }
+    }
+
+    let with_code = |code: u8| endpoints.iter().filter(move |e| e.code == code);
+    let drivers: Vec<&str> = with_code(step.read_from)
+        .filter(|e| e.readable)
+        .map(|e| e.name)
+        .collect();
+    match drivers.len() {
+        0 => messages.push(match with_code(step.read_from).next() {
+            Some(e) => format!("reads from {}, which can't drive the bus", e.name),
+            None => format!("reads from unknown endpoint {}", step.read_from),
+        }),
+        1 => (),
+        _ => messages.push(format!("bus driven by both {}", drivers.join(" and "))),
+    }
+    let latches: Vec<&str> = with_code(step.write_to)
+        .filter(|e| e.writable)
+        .map(|e| e.name)
+        .collect();
+    match latches.len() {
+        0 => messages.push(match with_code(step.write_to).next() {
+            Some(e) => format!("writes to {}, which is read-only", e.name),
+            None => format!("writes to unknown endpoint {}", step.write_to),
+        }),
+        1 => (),
+        _ => messages.push(format!("bus latched by both {}", latches.join(" and "))),
+    }
+    messages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn endpoint(name: &'static str, code: u8, readable: bool, writable: bool) -> Endpoint {
+        Endpoint {
+            name,
+            short_name: name,
+            code,
+            readable,
+            writable,
+        }
+    }
+
+    fn test_endpoints() -> Vec<Endpoint> {
+        vec![
+            endpoint("REGISTER_A", 0, true, true),
+            endpoint("OUT", 1, false, true),
+            endpoint("ACC", 2, true, false),
+            endpoint("IN", 3, true, false),
+            endpoint("PORT", 3, true, false),
+        ]
+    }
+
+    fn describe(issues: &[Issue]) -> Vec<String> {
+        issues.iter().map(|issue| issue.to_string()).collect()
+    }
+
+    #[test]
+    fn register_map_reports_shared_codes() {
+        assert_eq!(
+            vec!["register map: IN and PORT share code 3"],
+            describe(&check_register_map(&test_endpoints()))
+        );
+    }
+
+    #[test]
+    fn register_map_reports_accumulator_and_memory_address() {
+        assert_eq!(
+            vec!["register map: ACCUMULATOR and MEMORY_ADDRESS share code 6"],
+            describe(&check_register_map(&ENDPOINTS))
+        );
+    }
+
+    #[test]
+    fn step_reports_values_wider_than_their_field() {
+        let step = ControlWord {
+            read_from: 8,
+            alu_shift: 4,
+            halt: true,
+            ..ControlWord::empty()
+        };
+        assert_eq!(
+            vec![
+                "read_from value 8 does not fit into 3 bits",
+                "alu_shift value 4 does not fit into 2 bits",
+                "reads from unknown endpoint 8",
+            ],
+            check_step(&step, &test_endpoints())
+        );
+    }
+
+    #[test]
+    fn step_reports_wrong_direction() {
+        let step = ControlWord {
+            read_from: 1,
+            write_to: 2,
+            ..ControlWord::empty()
+        };
+        assert_eq!(
+            vec![
+                "reads from OUT, which can't drive the bus",
+                "writes to ACC, which is read-only",
+            ],
+            check_step(&step, &test_endpoints())
+        );
+    }
+
+    #[test]
+    fn step_reports_two_bus_drivers() {
+        let step = ControlWord {
+            read_from: 3,
+            ..ControlWord::empty()
+        };
+        assert_eq!(
+            vec!["bus driven by both IN and PORT"],
+            check_step(&step, &test_endpoints())
+        );
+    }
+
+    #[test]
+    fn sequence_reports_missing_fetch_and_step_reset() {
+        let steps = [ControlWord {
+            halt: true,
+            ..ControlWord::empty()
+        }];
+        assert_eq!(
+            vec![
+                "Hlt: does not start with the fetch cycle",
+                "Hlt: never resets the step counter",
+            ],
+            describe(&check_sequence("Hlt", &steps, &test_endpoints()))
+        );
+    }
+
+    #[test]
+    fn sequence_reports_unreachable_steps() {
+        let mut steps = fetch_steps();
+        steps.push(ControlWord {
+            step_reset: true,
+            ..ControlWord::empty()
+        });
+        steps.push(ControlWord::empty());
+        assert_eq!(
+            vec!["Nop: steps after 2 are unreachable because of step_reset"],
+            describe(&check_sequence("Nop", &steps, &ENDPOINTS))
+        );
+    }
+
+    #[test]
+    fn every_instruction_passes() {
+        for keyword in Keyword::all() {
+            let name = format!("{:?}", keyword);
+            let issues = check_sequence(&name, &keyword.control_words(), &ENDPOINTS);
+            assert_eq!(Vec::<String>::new(), describe(&issues));
+        }
+    }
+}
@@ -0,0 +1,453 @@
+use crate::assembler::{self, json_string};
+use crate::{describe_line_error, program};
+use nom::branch::alt;
+use nom::bytes::complete::tag;
+use nom::character::complete::{char, digit1, multispace0, one_of};
+use nom::combinator::{all_consuming, map, map_res, opt, recognize, value};
+use nom::error::ErrorKind;
+use nom::multi::separated_list;
+use nom::sequence::{delimited, pair, preceded, separated_pair, tuple};
+use nom::{Err, IResult};
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+/// A JSON value as far as the language server needs one. Objects keep their
+/// members in order.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// The member `key` of an object.
+    fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, member)| member),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(text) => Some(text),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Number(n) => write!(f, "{}", n),
+            Json::String(text) => write!(f, "{}", json_string(text)),
+            Json::Array(items) => {
+                let items: Vec<String> = items.iter().map(ToString::to_string).collect();
+                write!(f, "[{}]", items.join(","))
+            }
+            Json::Object(members) => {
+                let members: Vec<String> = members
+                    .iter()
+                    .map(|(name, member)| format!("{}:{}", json_string(name), member))
+                    .collect();
+                write!(f, "{{{}}}", members.join(","))
+            }
+        }
+    }
+}
+
+/// The characters of a string literal up to the closing quote, with escapes
+/// replaced. Unpaired surrogates become U+FFFD.
+fn json_string_literal(input: &str) -> IResult<&str, String> {
+    let (mut rest, _) = char('"')(input)?;
+    let mut text = String::new();
+    loop {
+        let mut chars = rest.chars();
+        match chars.next() {
+            Some('"') => return Ok((chars.as_str(), text)),
+            Some('\\') => {
+                let escape = chars.next();
+                rest = chars.as_str();
+                match escape {
+                    Some('"') => text.push('"'),
+                    Some('\\') => text.push('\\'),
+                    Some('/') => text.push('/'),
+                    Some('b') => text.push('\u{8}'),
+                    Some('f') => text.push('\u{c}'),
+                    Some('n') => text.push('\n'),
+                    Some('r') => text.push('\r'),
+                    Some('t') => text.push('\t'),
+                    Some('u') => {
+                        let (after, unit) = utf16_unit(rest)?;
+                        rest = after;
+                        let low = preceded(tag("\\u"), utf16_unit)(rest);
+                        let c = match low {
+                            Ok((after, low)) if (0xD800..0xDC00).contains(&unit) => {
+                                rest = after;
+                                let code = 0x10000 + ((unit - 0xD800) << 10) + (low - 0xDC00);
+                                std::char::from_u32(code)
+                            }
+                            _ => std::char::from_u32(unit),
+                        };
+                        text.push(c.unwrap_or('\u{FFFD}'));
+                    }
+                    _ => return Err(Err::Error((rest, ErrorKind::Escaped))),
+                }
+            }
+            Some(c) => {
+                text.push(c);
+                rest = chars.as_str();
+            }
+            None => return Err(Err::Error((rest, ErrorKind::Eof))),
+        }
+    }
+}
+
+fn utf16_unit(input: &str) -> IResult<&str, u32> {
+    if input.len() < 4 || !input.is_char_boundary(4) {
+        return Err(Err::Error((input, ErrorKind::HexDigit)));
+    }
+    match u32::from_str_radix(&input[..4], 16) {
+        Ok(unit) => Ok((&input[4..], unit)),
+        Err(_) => Err(Err::Error((input, ErrorKind::HexDigit))),
+    }
+}
+
+fn json_number(input: &str) -> IResult<&str, f64> {
+    map_res(
+        recognize(tuple((
+            opt(char('-')),
+            digit1,
+            opt(pair(char('.'), digit1)),
+            opt(tuple((one_of("eE"), opt(one_of("+-")), digit1))),
+        ))),
+        str::parse,
+    )(input)
+}
+
+fn json_value(input: &str) -> IResult<&str, Json> {
+    delimited(
+        multispace0,
+        alt((
+            value(Json::Null, tag("null")),
+            value(Json::Bool(true), tag("true")),
+            value(Json::Bool(false), tag("false")),
+            map(json_number, Json::Number),
+            map(json_string_literal, Json::String),
+            map(
+                delimited(
+                    char('['),
+                    separated_list(char(','), json_value),
+                    preceded(multispace0, char(']')),
+                ),
+                Json::Array,
+            ),
+            map(
+                delimited(
+                    char('{'),
+                    separated_list(
+                        char(','),
+                        separated_pair(
+                            delimited(multispace0, json_string_literal, multispace0),
+                            char(':'),
+                            json_value,
+                        ),
+                    ),
+                    preceded(multispace0, char('}')),
+                ),
+                Json::Object,
+            ),
+        )),
+        multispace0,
+    )(input)
+}
+
+/// Parses a complete JSON document.
+pub fn parse_json(input: &str) -> Option<Json> {
+    all_consuming(json_value)(input).ok().map(|(_, json)| json)
+}
+
+/// Reads one message framed by a `Content-Length` header, or `None` at the end
+/// of the input.
+fn read_message(reader: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+    let length = length.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "message without Content-Length")
+    })?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    String::from_utf8(body)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn write_message(writer: &mut impl Write, message: &Json) -> io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()
+}
+
+fn object(members: Vec<(&str, Json)>) -> Json {
+    Json::Object(
+        members
+            .into_iter()
+            .map(|(name, member)| (String::from(name), member))
+            .collect(),
+    )
+}
+
+/// LSP severities: 1 is an error, 2 a warning.
+fn diagnostic(source: &str, line: usize, severity: u8, message: String) -> Json {
+    let length = source.lines().nth(line - 1).map_or(0, str::len);
+    let position = |character| {
+        object(vec![
+            ("line", Json::Number((line - 1) as f64)),
+            ("character", Json::Number(character as f64)),
+        ])
+    };
+    object(vec![
+        (
+            "range",
+            object(vec![("start", position(0)), ("end", position(length))]),
+        ),
+        ("severity", Json::Number(f64::from(severity))),
+        ("source", Json::String(String::from("assembler-8bit"))),
+        ("message", Json::String(message)),
+    ])
+}
+
+/// The errors and warnings `check` would print for `source`. Parsing and
+/// assembling both stop at the first error.
+fn diagnostics(source: &str) -> Vec<Json> {
+    let lines = match program(source) {
+        Ok(lines) => lines,
+        Err(e) => {
+            let prefix = format!("line {}: ", e.0);
+            let message = describe_line_error(&e);
+            let message = message.trim_start_matches(&prefix);
+            return vec![diagnostic(source, e.0, 1, String::from(message))];
+        }
+    };
+    match assembler::assemble(&lines) {
+        Ok(program) => program
+            .warnings
+            .into_iter()
+            .map(|warning| diagnostic(source, warning.line, 2, warning.message))
+            .collect(),
+        Err(e) => vec![diagnostic(source, e.line, 1, e.message)],
+    }
+}
+
+fn publish_diagnostics(writer: &mut impl Write, uri: &Json, source: &str) -> io::Result<()> {
+    write_message(
+        writer,
+        &object(vec![
+            ("jsonrpc", Json::String(String::from("2.0"))),
+            (
+                "method",
+                Json::String(String::from("textDocument/publishDiagnostics")),
+            ),
+            (
+                "params",
+                object(vec![
+                    ("uri", uri.clone()),
+                    ("diagnostics", Json::Array(diagnostics(source))),
+                ]),
+            ),
+        ]),
+    )
+}
+
+fn response(id: &Json, result: Json) -> Json {
+    object(vec![
+        ("jsonrpc", Json::String(String::from("2.0"))),
+        ("id", id.clone()),
+        ("result", result),
+    ])
+}
+
+fn error_response(id: &Json, code: i32, message: &str) -> Json {
+    object(vec![
+        ("jsonrpc", Json::String(String::from("2.0"))),
+        ("id", id.clone()),
+        (
+            "error",
+            object(vec![
+                ("code", Json::Number(f64::from(code))),
+                ("message", Json::String(String::from(message))),
+            ]),
+        ),
+    ])
+}
+
+/// Runs a language server until the client sends `exit` or closes the input.
+/// Documents are synced in full, and every change publishes the diagnostics
+/// of the whole document.
+pub fn serve(mut reader: impl BufRead, mut writer: impl Write) -> io::Result<()> {
+    while let Some(body) = read_message(&mut reader)? {
+        let message = match parse_json(&body) {
+            Some(message) => message,
+            None => {
+                write_message(
+                    &mut writer,
+                    &error_response(&Json::Null, -32700, "parse error"),
+                )?;
+                continue;
+            }
+        };
+        let params = message.get("params");
+        let document = params.and_then(|params| params.get("textDocument"));
+        let uri = document.and_then(|document| document.get("uri"));
+        match (
+            message.get("method").and_then(Json::as_str),
+            message.get("id"),
+        ) {
+            (Some("initialize"), Some(id)) => {
+                let capabilities = object(vec![("textDocumentSync", Json::Number(1.0))]);
+                let result = object(vec![("capabilities", capabilities)]);
+                write_message(&mut writer, &response(id, result))?;
+            }
+            (Some("textDocument/didOpen"), None) => {
+                let text = document.and_then(|document| document.get("text"));
+                if let (Some(uri), Some(text)) = (uri, text.and_then(Json::as_str)) {
+                    publish_diagnostics(&mut writer, uri, text)?;
+                }
+            }
+            (Some("textDocument/didChange"), None) => {
+                let text = match params.and_then(|params| params.get("contentChanges")) {
+                    Some(Json::Array(changes)) => changes.last().and_then(|c| c.get("text")),
+                    _ => None,
+                };
+                if let (Some(uri), Some(text)) = (uri, text.and_then(Json::as_str)) {
+                    publish_diagnostics(&mut writer, uri, text)?;
+                }
+            }
+            (Some("shutdown"), Some(id)) => write_message(&mut writer, &response(id, Json::Null))?,
+            (Some("exit"), None) => return Ok(()),
+            (_, Some(id)) => {
+                write_message(&mut writer, &error_response(id, -32601, "method not found"))?
+            }
+            (_, None) => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(messages: &[&str]) -> Vec<u8> {
+        messages
+            .iter()
+            .flat_map(|body| format!("Content-Length: {}\r\n\r\n{}", body.len(), body).into_bytes())
+            .collect()
+    }
+
+    fn responses(output: &[u8]) -> Vec<Json> {
+        let mut reader = output;
+        let mut responses = Vec::new();
+        while let Some(body) = read_message(&mut reader).unwrap() {
+            responses.push(parse_json(&body).unwrap());
+        }
+        responses
+    }
+
+    #[test]
+    fn parses_json() {
+        let json = parse_json(r#" {"a": [1, -2.5e1, true, null], "b": "x\né😀"} "#);
+        let expected = Json::Object(vec![
+            (
+                String::from("a"),
+                Json::Array(vec![
+                    Json::Number(1.0),
+                    Json::Number(-25.0),
+                    Json::Bool(true),
+                    Json::Null,
+                ]),
+            ),
+            (String::from("b"), Json::String(String::from("x\né😀"))),
+        ]);
+        assert_eq!(Some(expected), json);
+        assert_eq!(Some(Json::Array(vec![])), parse_json("[ ]"));
+        assert_eq!(None, parse_json("{\"a\": 1,}"));
+    }
+
+    #[test]
+    fn writes_json_back() {
+        let json = r#"{"id":7,"result":{"items":["a\"b",false,null]}}"#;
+        assert_eq!(json, parse_json(json).unwrap().to_string());
+    }
+
+    #[test]
+    fn scripted_session_publishes_diagnostics() {
+        let input = frame(&[
+            r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#,
+            r#"{"jsonrpc":"2.0","method":"initialized","params":{}}"#,
+            r#"{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{"textDocument":{"uri":"file:///a.asm","languageId":"asm","version":1,"text":"nop\nbogus\n"}}}"#,
+            r#"{"jsonrpc":"2.0","method":"textDocument/didChange","params":{"textDocument":{"uri":"file:///a.asm","version":2},"contentChanges":[{"text":"ldi a, 200\n"}]}}"#,
+            r#"{"jsonrpc":"2.0","id":2,"method":"textDocument/hover","params":{}}"#,
+            r#"{"jsonrpc":"2.0","id":3,"method":"shutdown"}"#,
+            r#"{"jsonrpc":"2.0","method":"exit"}"#,
+            r#"{"jsonrpc":"2.0","id":4,"method":"shutdown"}"#,
+        ]);
+        let mut output = Vec::new();
+        serve(&input[..], &mut output).unwrap();
+        let responses = responses(&output);
+        assert_eq!(5, responses.len());
+
+        let sync = responses[0]
+            .get("result")
+            .and_then(|r| r.get("capabilities"));
+        assert_eq!(
+            Some(&Json::Number(1.0)),
+            sync.and_then(|c| c.get("textDocumentSync"))
+        );
+
+        let diagnostics = |response: &Json| match response.get("params").unwrap().get("diagnostics")
+        {
+            Some(Json::Array(diagnostics)) => diagnostics.clone(),
+            _ => panic!("no diagnostics in {}", response),
+        };
+        let errors = diagnostics(&responses[1]);
+        assert_eq!(1, errors.len());
+        assert_eq!(
+            r#"{"range":{"start":{"line":1,"character":0},"end":{"line":1,"character":5}},"severity":1,"source":"assembler-8bit","message":"can't parse `bogus`"}"#,
+            errors[0].to_string()
+        );
+        let warnings = diagnostics(&responses[2]);
+        assert_eq!(1, warnings.len());
+        assert_eq!(Some(&Json::Number(2.0)), warnings[0].get("severity"));
+
+        assert_eq!(
+            Some(&Json::Number(-32601.0)),
+            responses[3].get("error").and_then(|e| e.get("code"))
+        );
+        assert_eq!(
+            r#"{"jsonrpc":"2.0","id":3,"result":null}"#,
+            responses[4].to_string()
+        );
+    }
+}
@@ -1,21 +1,37 @@
+mod assembler;
+mod diff;
+mod display;
+mod doc;
+mod format;
+mod lint;
+mod lsp;
 mod microcode;
 mod output_datastructures;
+mod vcd;
 
+use crate::microcode::{Keyword, MicrocodeConfig, MovFrom, MovTo, GPR};
 use crate::output_datastructures::ControlWord;
 use nom::branch::alt;
-use nom::bytes::complete::{tag, tag_no_case, take_while1, take_while_m_n};
+use nom::bytes::complete::{tag, tag_no_case, take_while1};
 use nom::character::complete::digit1;
+use nom::character::complete::not_line_ending;
 use nom::character::complete::one_of;
-use nom::character::is_digit;
+use nom::character::complete::{space0, space1};
 use nom::character::is_hex_digit;
-use nom::character::is_space;
+use nom::combinator::{all_consuming, map, not, opt, recognize, value, verify};
 use nom::error::ErrorKind;
-use nom::sequence::preceded;
+use nom::multi::many0;
+use nom::sequence::{delimited, preceded, separated_pair, terminated, tuple};
 use nom::Err;
 use nom::IResult;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
 
 fn identifier(input: &str) -> IResult<&str, &str> {
-    one_of("_abcdefghijklmnopqrstuvwxyz")(input)?;
+    one_of("_abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ")(input)?;
     take_while1(|c: char| c.is_ascii_alphanumeric() || c == '_')(input)
 }
 
@@ -25,8 +41,18 @@ fn label_def(input: &str) -> IResult<&str, &str> {
     Ok((remaining, identifier))
 }
 
+fn is_identifier_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Matches `name` case-insensitively, but only as a whole word, so `or` does
+/// not match the start of `out`.
+fn word<'a>(name: &'static str) -> impl Fn(&'a str) -> IResult<&'a str, &'a str> {
+    terminated(tag_no_case(name), not(take_while1(is_identifier_char)))
+}
+
 fn hlt(input: &str) -> IResult<&str, &str> {
-    tag("hlt")(input)
+    word("hlt")(input)
 }
 
 fn dec_u8(input: &str) -> IResult<&str, u8> {
@@ -38,6 +64,17 @@ fn dec_u8(input: &str) -> IResult<&str, u8> {
     Ok((remaining, number))
 }
 
+/// A negative decimal number, encoded as its two's complement byte. Only
+/// values that fit into an `i8` are accepted.
+fn neg_dec_u8(input: &str) -> IResult<&str, u8> {
+    let (remaining, number) = recognize(preceded(tag("-"), digit1))(input)?;
+    let number: i8 = match number.parse() {
+        Ok(i) => i,
+        _ => return Err(Err::Error((input, ErrorKind::Digit))),
+    };
+    Ok((remaining, number as u8))
+}
+
 fn hex_u8(input: &str) -> IResult<&str, u8> {
     let (remaining, number) = preceded(
         tag_no_case("0x"),
@@ -62,42 +99,770 @@ fn bin_u8(input: &str) -> IResult<&str, u8> {
     Ok((remaining, number))
 }
 
+/// A single character of a character or string literal, which must not be
+/// `delimiter` unless escaped. Supported escapes are `\n`, `\r`, `\t`, `\0`,
+/// `\\`, `\'` and `\"`. The display only knows ASCII, so anything else is a
+/// failure pointing at the offending character instead of a silent mismatch.
+fn literal_char<'a>(delimiter: char) -> impl Fn(&'a str) -> IResult<&'a str, u8> {
+    move |input: &'a str| {
+        let mut chars = input.chars();
+        let c = match chars.next() {
+            Some(c) if c == delimiter => return Err(Err::Error((input, ErrorKind::Char))),
+            Some('\\') => match chars.next() {
+                Some('n') => '\n',
+                Some('r') => '\r',
+                Some('t') => '\t',
+                Some('0') => '\0',
+                Some('\\') => '\\',
+                Some('\'') => '\'',
+                Some('"') => '"',
+                _ => return Err(Err::Failure((&input[1..], ErrorKind::Escaped))),
+            },
+            Some(c) if c.is_ascii() => c,
+            Some(_) => return Err(Err::Failure((input, ErrorKind::Char))),
+            None => return Err(Err::Error((input, ErrorKind::Eof))),
+        };
+        Ok((chars.as_str(), c as u8))
+    }
+}
+
+fn char_u8(input: &str) -> IResult<&str, u8> {
+    delimited(tag("'"), literal_char('\''), tag("'"))(input)
+}
+
+fn string_literal(input: &str) -> IResult<&str, Vec<u8>> {
+    delimited(tag("\""), many0(literal_char('"')), tag("\""))(input)
+}
+
 fn memory_location(input: &str) -> IResult<&str, u8> {
-    alt((hex_u8, bin_u8, dec_u8))(input)
+    alt((hex_u8, bin_u8, dec_u8, char_u8))(input)
+}
+
+/// A value loaded into a register, which unlike an address may be negative.
+fn immediate(input: &str) -> IResult<&str, u8> {
+    alt((memory_location, neg_dec_u8))(input)
+}
+
+/// The operand of `ldi`. Decimal numbers are signed bytes, so 128 to 255
+/// written in decimal only fit as unsigned. Hex, binary and characters are
+/// plain bytes and fit either way.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Immediate {
+    value: u8,
+    unsigned_only: bool,
+}
+
+fn ldi_immediate(input: &str) -> IResult<&str, Immediate> {
+    alt((
+        map(verify(dec_u8, |value| *value > i8::MAX as u8), |value| {
+            Immediate {
+                value,
+                unsigned_only: true,
+            }
+        }),
+        map(immediate, |value| Immediate {
+            value,
+            unsigned_only: false,
+        }),
+    ))(input)
+}
+
+fn comment(input: &str) -> IResult<&str, &str> {
+    preceded(alt((tag(";"), tag("//"))), not_line_ending)(input)
+}
+
+fn gpr(input: &str) -> IResult<&str, GPR> {
+    alt((
+        value(GPR::A, word("a")),
+        value(GPR::B, word("b")),
+        value(GPR::C, word("c")),
+        value(GPR::D, word("d")),
+    ))(input)
+}
+
+fn mov_from(input: &str) -> IResult<&str, MovFrom> {
+    alt((
+        value(MovFrom::A, word("a")),
+        value(MovFrom::B, word("b")),
+        value(MovFrom::C, word("c")),
+        value(MovFrom::D, word("d")),
+        value(MovFrom::BS, word("bs")),
+        value(MovFrom::Acc, word("acc")),
+    ))(input)
+}
+
+fn mov_to(input: &str) -> IResult<&str, MovTo> {
+    alt((
+        value(MovTo::A, word("a")),
+        value(MovTo::B, word("b")),
+        value(MovTo::C, word("c")),
+        value(MovTo::D, word("d")),
+        value(MovTo::BS, word("bs")),
+        value(MovTo::Out, word("out")),
+    ))(input)
+}
+
+fn comma(input: &str) -> IResult<&str, &str> {
+    delimited(space0, tag(","), space0)(input)
+}
+
+/// Operands are written destination first, like `mov a, b` for A := B.
+fn two_gpr<'a>(name: &'static str) -> impl Fn(&'a str) -> IResult<&'a str, (GPR, GPR)> {
+    preceded(
+        terminated(word(name), space1),
+        separated_pair(gpr, comma, gpr),
+    )
+}
+
+fn one_gpr<'a>(name: &'static str) -> impl Fn(&'a str) -> IResult<&'a str, GPR> {
+    preceded(terminated(word(name), space1), gpr)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Target<'a> {
+    Address(u8),
+    Label(&'a str),
+}
+
+fn target(input: &str) -> IResult<&str, Target<'_>> {
+    alt((
+        map(memory_location, Target::Address),
+        map(identifier, Target::Label),
+    ))(input)
+}
+
+fn jump<'a>(name: &'static str) -> impl Fn(&'a str) -> IResult<&'a str, Target<'a>> {
+    preceded(terminated(word(name), space1), target)
+}
+
+/// An instruction or data directive as written in the source. Jumps and memory
+/// accesses may still refer to labels, which can only be turned into a
+/// `Keyword` once all addresses are known.
+#[derive(Clone, Debug, PartialEq)]
+enum Instruction<'a> {
+    Keyword(Keyword),
+    Ldi(GPR, Immediate),
+    Jmp(Target<'a>),
+    Jc(Target<'a>),
+    Jz(Target<'a>),
+    Ld(GPR, Target<'a>),
+    St(Target<'a>, GPR),
+    Data(Vec<u8>),
+    Bank(u8),
+    Pseudo(Pseudo<'a>),
+}
+
+impl<'a> Instruction<'a> {
+    /// The `Keyword` for instructions that don't depend on where anything is
+    /// placed, i.e. that have no label operands.
+    fn keyword(&self) -> Option<Keyword> {
+        match self {
+            Instruction::Keyword(keyword) => Some(keyword.clone()),
+            Instruction::Ldi(r, immediate) => Some(Keyword::Ldi(*r, immediate.value)),
+            Instruction::Jmp(Target::Address(address)) => Some(Keyword::Jmp(*address)),
+            Instruction::Jc(Target::Address(address)) => Some(Keyword::Jc(*address)),
+            Instruction::Jz(Target::Address(address)) => Some(Keyword::Jz(*address)),
+            Instruction::Ld(r, Target::Address(address)) => Some(Keyword::Ld(*r, *address)),
+            Instruction::St(Target::Address(address), r) => Some(Keyword::St(*address, *r)),
+            _ => None,
+        }
+    }
+}
+
+/// Assembler-level instructions that stand for a short sequence of real ones.
+/// The ALU only takes registers, so anything needing a constant takes a
+/// register the program has loaded with it, e.g. via `ldi`: `inc a, d` expects
+/// D to hold 1, `not a, d` expects D to hold 0xFF. `neg` needs a scratch
+/// register it may clobber.
+///
+/// There is no stack, so `call sub, r` stores the return address into the
+/// operand of the first `ret` after `sub`, which is a `jmp`, and jumps to
+/// `sub`. Only registers can be stored, so r is clobbered. A subroutine can't
+/// call itself, directly or through others.
+#[derive(Clone, Debug, PartialEq)]
+enum Pseudo<'a> {
+    Clr(GPR),
+    Inc(GPR, GPR),
+    Dec(GPR, GPR),
+    Not(GPR, GPR),
+    Neg(GPR, GPR),
+    Call(&'a str, GPR),
+    Ret,
+}
+
+/// Looks up the address of a label and of the operand of the `ret` following
+/// it.
+type Subroutine<'a> = dyn Fn(&str) -> Result<(u8, u8), String> + 'a;
+
+impl<'a> Pseudo<'a> {
+    /// The instructions placed at `address`.
+    fn expand(&self, address: u8, subroutine: &Subroutine<'_>) -> Result<Vec<Keyword>, String> {
+        Ok(match self {
+            Pseudo::Clr(r) => vec![Keyword::Xor(*r, *r)],
+            Pseudo::Inc(r, one) => vec![Keyword::Add(*r, *one)],
+            Pseudo::Dec(r, one) => vec![Keyword::Sub(*r, *one)],
+            Pseudo::Not(r, ones) => vec![Keyword::Xor(*r, *ones)],
+            Pseudo::Neg(r, scratch) => vec![
+                Keyword::Xor(*scratch, *scratch),
+                Keyword::Sub(*scratch, *r),
+                Keyword::Mov((*scratch).into(), (*r).into()),
+            ],
+            Pseudo::Call(label, scratch) => {
+                let (entry, ret) = subroutine(label)?;
+                let size = Keyword::Ldi(*scratch, 0).length()
+                    + Keyword::St(0, *scratch).length()
+                    + Keyword::Jmp(0).length();
+                vec![
+                    Keyword::Ldi(*scratch, address.wrapping_add(size)),
+                    Keyword::St(ret, *scratch),
+                    Keyword::Jmp(entry),
+                ]
+            }
+            Pseudo::Ret => vec![Keyword::Jmp(0)],
+        })
+    }
+}
+
+fn pseudo(input: &str) -> IResult<&str, Instruction<'_>> {
+    map(
+        alt((
+            map(one_gpr("clr"), Pseudo::Clr),
+            map(two_gpr("inc"), |(r, one)| Pseudo::Inc(r, one)),
+            map(two_gpr("dec"), |(r, one)| Pseudo::Dec(r, one)),
+            map(two_gpr("not"), |(r, ones)| Pseudo::Not(r, ones)),
+            map(
+                verify(two_gpr("neg"), |(r, scratch)| r != scratch),
+                |(r, scratch)| Pseudo::Neg(r, scratch),
+            ),
+            map(
+                preceded(
+                    terminated(word("call"), space1),
+                    separated_pair(identifier, comma, gpr),
+                ),
+                |(label, scratch)| Pseudo::Call(label, scratch),
+            ),
+            value(Pseudo::Ret, word("ret")),
+        )),
+        Instruction::Pseudo,
+    )(input)
+}
+
+fn bracketed<'a, O, F>(parser: F) -> impl Fn(&'a str) -> IResult<&'a str, O>
+where
+    F: Fn(&'a str) -> IResult<&'a str, O>,
+{
+    delimited(
+        terminated(tag("["), space0),
+        parser,
+        preceded(space0, tag("]")),
+    )
+}
+
+/// `ld r, [addr]`, `ld r, [r2]` and `st [addr], r`.
+fn memory_access(input: &str) -> IResult<&str, Instruction<'_>> {
+    alt((
+        map(
+            preceded(
+                terminated(word("ld"), space1),
+                separated_pair(gpr, comma, bracketed(gpr)),
+            ),
+            |(r, address)| Instruction::Keyword(Keyword::LdInd(r, address)),
+        ),
+        map(
+            preceded(
+                terminated(word("ld"), space1),
+                separated_pair(gpr, comma, bracketed(target)),
+            ),
+            |(r, address)| Instruction::Ld(r, address),
+        ),
+        map(
+            preceded(
+                terminated(word("st"), space1),
+                separated_pair(bracketed(target), comma, gpr),
+            ),
+            |(address, r)| Instruction::St(address, r),
+        ),
+    ))(input)
+}
+
+fn instruction(input: &str) -> IResult<&str, Instruction<'_>> {
+    alt((
+        map(
+            preceded(
+                terminated(word("mov"), space1),
+                separated_pair(mov_to, comma, mov_from),
+            ),
+            |(to, from)| Instruction::Keyword(Keyword::Mov(from, to)),
+        ),
+        map(two_gpr("sub"), |(a, b)| {
+            Instruction::Keyword(Keyword::Sub(a, b))
+        }),
+        map(two_gpr("add"), |(a, b)| {
+            Instruction::Keyword(Keyword::Add(a, b))
+        }),
+        map(two_gpr("and"), |(a, b)| {
+            Instruction::Keyword(Keyword::And(a, b))
+        }),
+        map(two_gpr("or"), |(a, b)| {
+            Instruction::Keyword(Keyword::Or(a, b))
+        }),
+        map(two_gpr("xor"), |(a, b)| {
+            Instruction::Keyword(Keyword::Xor(a, b))
+        }),
+        map(two_gpr("cmp"), |(a, b)| {
+            Instruction::Keyword(Keyword::Cmp(a, b))
+        }),
+        map(one_gpr("shl"), |a| Instruction::Keyword(Keyword::Shl(a))),
+        map(one_gpr("shr"), |a| Instruction::Keyword(Keyword::Shr(a))),
+        map(jump("jmp"), Instruction::Jmp),
+        map(jump("jc"), Instruction::Jc),
+        map(jump("jz"), Instruction::Jz),
+        value(Instruction::Keyword(Keyword::Hlt), hlt),
+        value(Instruction::Keyword(Keyword::Nop), word("nop")),
+        map(
+            preceded(
+                terminated(word("ldi"), space1),
+                separated_pair(gpr, comma, ldi_immediate),
+            ),
+            |(r, imm)| Instruction::Ldi(r, imm),
+        ),
+        memory_access,
+    ))(input)
+}
+
+/// `.ascii "text"` emits the bytes of the string, `.asciz "text"` appends a
+/// terminating zero. `.bank n` places everything after it into memory bank n,
+/// a decimal, hex or binary number.
+fn directive(input: &str) -> IResult<&str, Instruction<'_>> {
+    alt((
+        map(
+            preceded(terminated(word(".ascii"), space1), string_literal),
+            Instruction::Data,
+        ),
+        map(
+            preceded(terminated(word(".asciz"), space1), string_literal),
+            |mut bytes| {
+                bytes.push(0);
+                Instruction::Data(bytes)
+            },
+        ),
+        map(
+            preceded(
+                terminated(word(".bank"), space1),
+                alt((hex_u8, bin_u8, dec_u8)),
+            ),
+            Instruction::Bank,
+        ),
+    ))(input)
+}
+
+#[derive(Debug, PartialEq)]
+struct Line<'a> {
+    label: Option<&'a str>,
+    instruction: Option<Instruction<'a>>,
+}
+
+/// A single source line: an optional label, an optional instruction and an
+/// optional `;` or `//` comment, separated by any amount of whitespace.
+fn line(input: &str) -> IResult<&str, Line<'_>> {
+    let (remaining, (_, label, _, instruction, _, _)) = all_consuming(tuple((
+        space0,
+        opt(label_def),
+        space0,
+        opt(alt((directive, pseudo, instruction))),
+        space0,
+        opt(comment),
+    )))(input)?;
+    Ok((remaining, Line { label, instruction }))
+}
+
+/// The 1-based number of a line that failed to parse, and the parser error.
+type LineError<'a> = (usize, Err<(&'a str, ErrorKind)>);
+
+/// Parses a whole source file, stopping at the first line that does not parse.
+fn program(input: &str) -> Result<Vec<Line<'_>>, LineError<'_>> {
+    input
+        .lines()
+        .enumerate()
+        .map(|(number, l)| match line(l) {
+            Ok((_, parsed)) => Ok(parsed),
+            Err(e) => Err((number + 1, e)),
+        })
+        .collect()
+}
+
+/// Describes a `LineError` for the user, e.g. "line 4: can't parse `bogus`".
+fn describe_line_error(error: &LineError<'_>) -> String {
+    let (number, error) = error;
+    match error {
+        Err::Failure((rest, ErrorKind::Char)) if !rest.is_empty() => format!(
+            "line {}: only ASCII characters are allowed, found `{}`",
+            number,
+            rest.chars().next().unwrap()
+        ),
+        Err::Failure((rest, ErrorKind::Escaped)) if !rest.is_empty() => format!(
+            "line {}: unknown escape sequence `\\{}`",
+            number,
+            rest.chars().next().unwrap()
+        ),
+        Err::Error((rest, _)) | Err::Failure((rest, _)) => {
+            format!("line {}: can't parse `{}`", number, rest)
+        }
+        Err::Incomplete(_) => format!("line {}: incomplete", number),
+    }
 }
 
 fn main() {
-    /*let a = tag("a");
-    let b = tag("b");
-    let c = tag("c");
-    let d = tag("d");
-    let out = tag("out");
-    let bs = tag("bs");
+    let mut args: Vec<String> = env::args().collect();
+    let config = take_microcode_options(&mut args);
+    match args.get(1).map(String::as_str) {
+        Some("display-rom") if args.len() == 3 => write_file(&args[2], &display::decoder_rom()),
+        Some("display") if args.len() == 4 => show_display(&args[2], &args[3]),
+        Some("vcd") if args.len() == 4 => write_microcode_vcd(&args[2], &args[3], &config),
+        Some("check") if args.len() == 3 => {
+            assemble_source(&args[2]);
+        }
+        Some("assemble") if args.len() == 4 => write_program(&args[2], &args[3]),
+        Some("fmt") if args.len() == 3 => format_source(&args[2], false),
+        Some("fmt") if args.len() == 4 && args[2] == "--check" => format_source(&args[3], true),
+        Some("lint") if args.len() == 2 => lint_microcode(&config),
+        Some("lsp") if args.len() == 2 => {
+            let stdin = io::stdin();
+            if let Err(e) = lsp::serve(stdin.lock(), io::stdout()) {
+                eprintln!("language server stopped: {}", e);
+                process::exit(1);
+            }
+        }
+        Some("microcode") if args.len() == 4 && args[2] == "doc" => {
+            document_microcode(&args[3], &config)
+        }
+        Some("microcode") if args.len() == 3 && args[2] == "isa" => {
+            print!("{}", doc::isa_markdown(&generated_microcode(&config)))
+        }
+        Some("microcode") if args.len() == 4 && args[2] == "dump" => {
+            let dump = diff::dump(&generated_microcode(&config));
+            write_file(&args[3], dump.as_bytes())
+        }
+        Some("microcode") if (4..=5).contains(&args.len()) && args[2] == "diff" => {
+            diff_microcode(&args[3], args.get(4), &config)
+        }
+        Some("microcode") if args.len() == 6 && args[2] == "rom" => {
+            write_microcode_rom(&args[3..], &config)
+        }
+        Some("microcode") if args.len() == 6 && args[2] == "diff-rom" => {
+            diff_microcode_rom(&args[3..], &config)
+        }
+        _ => {
+            eprintln!("usage: {} display-rom <output file>", args[0]);
+            eprintln!(
+                "       {} display <unsigned|signed|hex|ascii> <value>",
+                args[0]
+            );
+            eprintln!("       {} vcd <instruction> <output file>", args[0]);
+            eprintln!("       {} check <source file>", args[0]);
+            eprintln!("       {} assemble <source file> <output file>", args[0]);
+            eprintln!("       {} fmt [--check] <source file>", args[0]);
+            eprintln!("       {} lint", args[0]);
+            eprintln!("       {} lsp", args[0]);
+            eprintln!("       {} microcode doc <markdown|html>", args[0]);
+            eprintln!("       {} microcode isa", args[0]);
+            eprintln!("       {} microcode dump <output file>", args[0]);
+            eprintln!("       {} microcode diff <old dump> [<new dump>]", args[0]);
+            eprintln!("       {} microcode rom <msb> <middle> <lsb>", args[0]);
+            eprintln!("       {} microcode diff-rom <msb> <middle> <lsb>", args[0]);
+            eprintln!("lint, vcd and microcode take [--step-counter-bits <n>] [--wrap-around]");
+            process::exit(2);
+        }
+    }
+}
+
+/// Parses and assembles a source file and reports the first line that
+/// doesn't parse or can't be assembled. Returns the source and the program.
+fn assemble_source(path: &str) -> (String, assembler::Program) {
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("could not read {}: {}", path, e);
+            process::exit(1);
+        }
+    };
+    let assembled = match program(&source) {
+        Ok(lines) => assembler::assemble(&lines).map_err(|e| e.to_string()),
+        Err(e) => Err(describe_line_error(&e)),
+    };
+    match assembled {
+        Ok(assembled) => {
+            for warning in &assembled.warnings {
+                eprintln!("{}: {}", path, warning);
+            }
+            (source, assembled)
+        }
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Formats a source file in place, or with `check` only reports the lines
+/// that are not formatted and fails if there are any.
+fn format_source(path: &str, check: bool) {
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("could not read {}: {}", path, e);
+            process::exit(1);
+        }
+    };
+    let formatted = match format::format(&source) {
+        Ok(formatted) => formatted,
+        Err(e) => {
+            eprintln!("{}: {}", path, describe_line_error(&e));
+            process::exit(1);
+        }
+    };
+    if formatted == source {
+        return;
+    }
+    if !check {
+        write_file(path, formatted.as_bytes());
+        return;
+    }
+    let mut lines = source.lines().zip(formatted.lines());
+    match lines.position(|(old, new)| old != new) {
+        Some(index) => eprintln!("{}: line {} is not formatted", path, index + 1),
+        None => eprintln!("{}: line endings are not formatted", path),
+    }
+    process::exit(1);
+}
 
-    let gpr = alt((a, b, c, d));
-    let params = alt((gpr, out, bs));
+/// Writes the memory image of a source file to `path`, and next to it the
+/// listing with the extension `.lst` and debug information for debuggers
+/// with the extension `.json`.
+fn write_program(source_path: &str, path: &str) {
+    let (listing_path, debug_path) = match side_files(path) {
+        Ok(paths) => paths,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(2);
+        }
+    };
+    let (source, program) = assemble_source(source_path);
+    write_file(path, &program.image);
+    write_file(
+        &listing_path.to_string_lossy(),
+        assembler::listing(&source, &program).as_bytes(),
+    );
+    write_file(
+        &debug_path.to_string_lossy(),
+        assembler::debug_json(source_path, &program).as_bytes(),
+    );
+}
 
-    let mov = tag("mov");
+/// The listing and debug file written next to the image at `path`. An image
+/// named like one of them would be overwritten, so it is refused.
+fn side_files(path: &str) -> Result<(PathBuf, PathBuf), String> {
+    let listing_path = Path::new(path).with_extension("lst");
+    let debug_path = Path::new(path).with_extension("json");
+    if Path::new(path) == listing_path || Path::new(path) == debug_path {
+        return Err(format!(
+            "{} would be overwritten by the listing or debug file, use another extension",
+            path
+        ));
+    }
+    Ok((listing_path, debug_path))
+}
 
-    let sub = tag("sub");
-    let add = tag("add");
-    let and = tag("and");
-    let or = tag("or");
-    let xor = tag("xor");
-    let cmp = tag("cmp");
+/// Removes the options of the microcode commands from `args`. The step
+/// counter width and wrap-around default to `MicrocodeConfig::default()`.
+fn take_microcode_options(args: &mut Vec<String>) -> MicrocodeConfig {
+    let mut config = MicrocodeConfig::default();
+    if let Some(index) = args.iter().position(|arg| arg == "--wrap-around") {
+        args.remove(index);
+        config.wrap_around = true;
+    }
+    if let Some(index) = args.iter().position(|arg| arg == "--step-counter-bits") {
+        match args.get(index + 1).and_then(|bits| bits.parse().ok()) {
+            Some(bits) => config.step_counter_bits = bits,
+            None => {
+                eprintln!("--step-counter-bits needs a number of bits");
+                process::exit(2);
+            }
+        }
+        args.drain(index..index + 2);
+    }
+    config
+}
+
+/// Prints how the display shows `value`, e.g. `display signed -1`.
+fn show_display(mode: &str, value: &str) {
+    let mode = match mode {
+        "unsigned" => display::DisplayMode::Unsigned,
+        "signed" => display::DisplayMode::Signed,
+        "hex" => display::DisplayMode::Hex,
+        "ascii" => display::DisplayMode::Ascii,
+        _ => {
+            eprintln!("unknown display mode {}", mode);
+            process::exit(2);
+        }
+    };
+    match all_consuming(immediate)(value) {
+        Ok((_, value)) => println!("{}", display::render(value, mode)),
+        Err(_) => {
+            eprintln!("not a byte: {}", value);
+            process::exit(2);
+        }
+    }
+}
 
-    let shl = tag("shl");
-    let shr = tag("shr");
+fn lint_microcode(config: &MicrocodeConfig) {
+    let issues = lint::check_all();
+    for issue in issues.iter() {
+        println!("{}", issue);
+    }
+    let generated = microcode::generate(config);
+    if let Err(e) = &generated {
+        println!("{}", e);
+    }
+    if !issues.is_empty() || generated.is_err() {
+        process::exit(1);
+    }
+}
 
-    let jmp = tag("jmp");
-    let jc = tag("jc");
-    let jz = tag("jz");
+/// Prints the microcode reference to stdout.
+fn document_microcode(format: &str, config: &MicrocodeConfig) {
+    let render = match format {
+        "markdown" => doc::markdown,
+        "html" => doc::html,
+        _ => {
+            eprintln!("unknown format {}, expected markdown or html", format);
+            process::exit(2);
+        }
+    };
+    print!("{}", render(&generated_microcode(config)));
+}
 
-    let hlt = tag("hlt");
-    */
+fn generated_microcode(config: &MicrocodeConfig) -> Vec<(Keyword, Vec<ControlWord>)> {
+    match microcode::generate(config) {
+        Ok(microcode) => microcode,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
+}
 
-    let test = ControlWord::empty();
-    println!("Hello, world!");
+fn read_dump(path: &str) -> Vec<diff::Entry> {
+    let parsed = fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|text| diff::parse_dump(&text));
+    match parsed {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("could not read {}: {}", path, e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Compares a dump with another one or with the current microcode. Exits with
+/// 1 if anything changed, like `diff` does.
+fn diff_microcode(old: &str, new: Option<&String>, config: &MicrocodeConfig) {
+    let old = read_dump(old);
+    let new = match new {
+        Some(path) => read_dump(path),
+        None => diff::entries(&generated_microcode(config)),
+    };
+    print_changes(&old, &new, config);
+}
+
+/// EEPROM images grow with the step counter, 16 bits already make them
+/// 16 MiB each.
+const MAX_ROM_STEP_COUNTER_BITS: u32 = 16;
+
+fn check_rom_step_counter(config: &MicrocodeConfig) {
+    if config.step_counter_bits > MAX_ROM_STEP_COUNTER_BITS {
+        eprintln!(
+            "ROM images need a step counter of at most {} bits",
+            MAX_ROM_STEP_COUNTER_BITS
+        );
+        process::exit(2);
+    }
+}
+
+/// Writes the images of the three microcode EEPROMs, most significant first.
+fn write_microcode_rom(paths: &[String], config: &MicrocodeConfig) {
+    check_rom_step_counter(config);
+    let images = diff::rom_images(&generated_microcode(config), config.step_counter_bits);
+    for (path, image) in paths.iter().zip(images.iter()) {
+        write_file(path, image);
+    }
+}
+
+/// Compares the three EEPROM images read back from the hardware, most
+/// significant first, with the current microcode.
+fn diff_microcode_rom(paths: &[String], config: &MicrocodeConfig) {
+    check_rom_step_counter(config);
+    let images: Vec<Vec<u8>> = paths
+        .iter()
+        .map(|path| match fs::read(path) {
+            Ok(image) => image,
+            Err(e) => {
+                eprintln!("could not read {}: {}", path, e);
+                process::exit(1);
+            }
+        })
+        .collect();
+    let images = [&images[0][..], &images[1][..], &images[2][..]];
+    let old = match diff::read_rom_images(images, config.step_counter_bits) {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("could not read ROM images: {}", e);
+            process::exit(1);
+        }
+    };
+    print_changes(&old, &diff::entries(&generated_microcode(config)), config);
+}
+
+/// Prints the changes between two versions of the microcode. Exits with 1 if
+/// anything changed, like `diff` does.
+fn print_changes(old: &[diff::Entry], new: &[diff::Entry], config: &MicrocodeConfig) {
+    let changes = diff::diff(old, new, config.step_counter_bits);
+    for change in changes.iter() {
+        println!("{}", change);
+    }
+    if !changes.is_empty() {
+        process::exit(1);
+    }
+}
+
+/// Dumps the microcode steps of a single instruction, e.g. `"add a, b"`, as
+/// the step counter of `config` runs through them.
+fn write_microcode_vcd(source: &str, path: &str, config: &MicrocodeConfig) {
+    let parsed = all_consuming(instruction)(source).ok();
+    let keyword = match parsed.and_then(|(_, parsed)| parsed.keyword()) {
+        Some(keyword) => keyword,
+        None => {
+            eprintln!("not an instruction with fixed operands: {}", source);
+            process::exit(1);
+        }
+    };
+    let steps = match microcode::fit_step_counter(&keyword, config) {
+        Ok(steps) => steps,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+    let vcd = vcd::write_vcd(&steps, config.step_counter_bits);
+    write_file(path, vcd.as_bytes());
+}
+
+fn write_file(path: &str, contents: &[u8]) {
+    if let Err(e) = fs::write(path, contents) {
+        eprintln!("could not write {}: {}", path, e);
+        process::exit(1);
+    }
 }
 
 #[cfg(test)]
@@ -105,6 +870,42 @@ mod tests {
     use super::*;
     use nom::Err;
 
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| String::from(*arg)).collect()
+    }
+
+    #[test]
+    fn side_files_replace_the_extension() {
+        assert_eq!(
+            Ok((PathBuf::from("out/p.lst"), PathBuf::from("out/p.json"))),
+            side_files("out/p.bin")
+        );
+        assert_eq!(
+            Ok((PathBuf::from("p.lst"), PathBuf::from("p.json"))),
+            side_files("p")
+        );
+    }
+
+    #[test]
+    fn side_files_refuse_to_overwrite_the_image() {
+        assert!(side_files("p.json").is_err());
+        assert!(side_files("p.lst").is_err());
+    }
+
+    #[test]
+    fn microcode_options_are_taken_from_args() {
+        let mut given = args(&["asm", "--step-counter-bits", "4", "lint", "--wrap-around"]);
+        let config = take_microcode_options(&mut given);
+        assert_eq!(args(&["asm", "lint"]), given);
+        assert_eq!(4, config.step_counter_bits);
+        assert!(config.wrap_around);
+
+        let mut given = args(&["asm", "lint"]);
+        let config = take_microcode_options(&mut given);
+        assert_eq!(3, config.step_counter_bits);
+        assert!(!config.wrap_around);
+    }
+
     #[test]
     fn memory_location_matches_numbers() {
         let input = "123";
@@ -114,6 +915,73 @@ mod tests {
         let input = "0b00001101";
         assert_eq!(memory_location(input), Ok(("", 0b00001101)));
     }
+    #[test]
+    fn memory_location_matches_characters() {
+        let input = "'A'";
+        assert_eq!(memory_location(input), Ok(("", b'A')));
+    }
+
+    #[test]
+    fn memory_location_rejects_negative_numbers() {
+        let input = "-1";
+        assert!(memory_location(input).is_err());
+    }
+
+    #[test]
+    fn immediate_matches_negative_numbers() {
+        let input = "-1";
+        assert_eq!(immediate(input), Ok(("", 0xFF)));
+        let input = "0x10";
+        assert_eq!(immediate(input), Ok(("", 0x10)));
+    }
+
+    #[test]
+    fn char_u8_matches_ascii_character() {
+        let input = "'0' lakfsdj";
+        assert_eq!(char_u8(input), Ok((" lakfsdj", b'0')));
+        let input = "'\\n'";
+        assert_eq!(char_u8(input), Ok(("", b'\n')));
+        let input = "'\\''";
+        assert_eq!(char_u8(input), Ok(("", b'\'')));
+        let input = "''";
+        assert_eq!(char_u8(input), Err(Err::Error(("'", ErrorKind::Char))));
+        let input = "'ab'";
+        assert_eq!(char_u8(input), Err(Err::Error(("b'", ErrorKind::Tag))));
+    }
+
+    #[test]
+    fn char_u8_rejects_non_ascii() {
+        let input = "'ä'";
+        assert_eq!(char_u8(input), Err(Err::Failure(("ä'", ErrorKind::Char))));
+        let input = "'\\q'";
+        assert_eq!(
+            char_u8(input),
+            Err(Err::Failure(("q'", ErrorKind::Escaped)))
+        );
+    }
+
+    #[test]
+    fn string_literal_matches_escapes() {
+        let input = "\"Hi\\t\\\"you\\\"\" rest";
+        assert_eq!(
+            string_literal(input),
+            Ok((" rest", b"Hi\t\"you\"".to_vec()))
+        );
+        let input = "\"\"";
+        assert_eq!(string_literal(input), Ok(("", vec![])));
+        let input = "\"open";
+        assert_eq!(string_literal(input), Err(Err::Error(("", ErrorKind::Tag))));
+    }
+
+    #[test]
+    fn string_literal_rejects_non_ascii() {
+        let input = "\"Grüße\"";
+        assert_eq!(
+            string_literal(input),
+            Err(Err::Failure(("üße\"", ErrorKind::Char)))
+        );
+    }
+
     #[test]
     fn dec_u8_matches_8bit_number() {
         let input = "123";
@@ -128,6 +996,25 @@ mod tests {
         assert_eq!(dec_u8(input), Ok((" lakfsdj", 12)));
     }
 
+    #[test]
+    fn neg_dec_u8_matches_8bit_twos_complement() {
+        let input = "-5";
+        assert_eq!(neg_dec_u8(input), Ok(("", 0b11111011)));
+        let input = "-128 lakfsdj";
+        assert_eq!(neg_dec_u8(input), Ok((" lakfsdj", 0x80)));
+        let input = "-0";
+        assert_eq!(neg_dec_u8(input), Ok(("", 0)));
+        let input = "-129";
+        assert_eq!(
+            neg_dec_u8(input),
+            Err(Err::Error((input, ErrorKind::Digit)))
+        );
+        let input = "5";
+        assert_eq!(neg_dec_u8(input), Err(Err::Error((input, ErrorKind::Tag))));
+        let input = "-x";
+        assert_eq!(neg_dec_u8(input), Err(Err::Error(("x", ErrorKind::Digit))));
+    }
+
     #[test]
     fn hex_u8_matches_8bit_number() {
         let input = "0x10";
@@ -180,12 +1067,413 @@ mod tests {
         assert_eq!(hlt(input), Ok((" asdf", "hlt")));
     }
 
+    #[test]
+    fn hlt_ignores_case() {
+        let input = "HlT";
+        assert_eq!(hlt(input), Ok(("", "HlT")));
+    }
+
+    #[test]
+    fn word_only_matches_whole_words() {
+        let input = "out";
+        assert_eq!(word("o")(input), Err(Err::Error(("ut", ErrorKind::Not))));
+        let input = "or, a";
+        assert_eq!(word("or")(input), Ok((", a", "or")));
+    }
+
+    #[test]
+    fn comment_matches_semicolon_and_double_slash() {
+        let input = "; hello";
+        assert_eq!(comment(input), Ok(("", " hello")));
+        let input = "// hello";
+        assert_eq!(comment(input), Ok(("", " hello")));
+        let input = "/ hello";
+        assert_eq!(comment(input), Err(Err::Error((input, ErrorKind::Tag))));
+    }
+
+    #[test]
+    fn instruction_parses_mov_destination_first() {
+        let input = "mov out, acc";
+        assert_eq!(
+            instruction(input),
+            Ok((
+                "",
+                Instruction::Keyword(Keyword::Mov(MovFrom::Acc, MovTo::Out))
+            ))
+        );
+    }
+
+    #[test]
+    fn instruction_ignores_case_and_whitespace_in_operands() {
+        let input = "ADD\tA ,b";
+        assert_eq!(
+            instruction(input),
+            Ok(("", Instruction::Keyword(Keyword::Add(GPR::A, GPR::B))))
+        );
+        let input = "Shr  D";
+        assert_eq!(
+            instruction(input),
+            Ok(("", Instruction::Keyword(Keyword::Shr(GPR::D))))
+        );
+    }
+
+    #[test]
+    fn instruction_parses_jump_targets() {
+        let input = "jmp 0x10";
+        assert_eq!(
+            instruction(input),
+            Ok(("", Instruction::Jmp(Target::Address(0x10))))
+        );
+        let input = "jz loop";
+        assert_eq!(
+            instruction(input),
+            Ok(("", Instruction::Jz(Target::Label("loop"))))
+        );
+    }
+
+    #[test]
+    fn instruction_parses_ldi_immediate() {
+        let byte = |value| Immediate {
+            value,
+            unsigned_only: false,
+        };
+        let input = "ldi b, 'x'";
+        assert_eq!(
+            instruction(input),
+            Ok(("", Instruction::Ldi(GPR::B, byte(b'x'))))
+        );
+        let input = "LDI a, -1";
+        assert_eq!(
+            instruction(input),
+            Ok(("", Instruction::Ldi(GPR::A, byte(0xFF))))
+        );
+        let input = "ldi a, 0x80";
+        assert_eq!(
+            instruction(input),
+            Ok(("", Instruction::Ldi(GPR::A, byte(0x80))))
+        );
+        let input = "ldi a, 128";
+        assert_eq!(
+            instruction(input),
+            Ok((
+                "",
+                Instruction::Ldi(
+                    GPR::A,
+                    Immediate {
+                        value: 128,
+                        unsigned_only: true
+                    }
+                )
+            ))
+        );
+    }
+
+    #[test]
+    fn instruction_rejects_negative_addresses() {
+        assert!(all_consuming(instruction)("jmp -1").is_err());
+        assert!(all_consuming(instruction)("ld a, [-5]").is_err());
+        assert!(all_consuming(instruction)("st [-5], a").is_err());
+    }
+
+    #[test]
+    fn instruction_parses_absolute_memory_access() {
+        let input = "ld a, [0x80]";
+        assert_eq!(
+            instruction(input),
+            Ok(("", Instruction::Ld(GPR::A, Target::Address(0x80))))
+        );
+        let input = "ST [ counter ] , d";
+        assert_eq!(
+            instruction(input),
+            Ok(("", Instruction::St(Target::Label("counter"), GPR::D)))
+        );
+    }
+
+    #[test]
+    fn instruction_parses_register_indirect_load() {
+        let input = "ld a, [c]";
+        assert_eq!(
+            instruction(input),
+            Ok(("", Instruction::Keyword(Keyword::LdInd(GPR::A, GPR::C))))
+        );
+    }
+
+    #[test]
+    fn instruction_keyword_needs_fixed_operands() {
+        assert_eq!(
+            Some(Keyword::Jz(0x10)),
+            Instruction::Jz(Target::Address(0x10)).keyword()
+        );
+        assert_eq!(
+            Some(Keyword::St(0x80, GPR::B)),
+            Instruction::St(Target::Address(0x80), GPR::B).keyword()
+        );
+        assert_eq!(None, Instruction::Jmp(Target::Label("loop")).keyword());
+        assert_eq!(None, Instruction::Bank(1).keyword());
+    }
+
+    #[test]
+    fn instruction_rejects_invalid_registers() {
+        let input = "mov acc, a";
+        assert!(instruction(input).is_err());
+        let input = "add a, out";
+        assert!(instruction(input).is_err());
+    }
+
+    #[test]
+    fn line_accepts_blank_lines() {
+        let expected = Line {
+            label: None,
+            instruction: None,
+        };
+        assert_eq!(line(""), Ok(("", expected)));
+        let expected = Line {
+            label: None,
+            instruction: None,
+        };
+        assert_eq!(line(" \t "), Ok(("", expected)));
+    }
+
+    #[test]
+    fn line_accepts_label_on_its_own() {
+        let input = "loop:   ; wait for input";
+        let expected = Line {
+            label: Some("loop"),
+            instruction: None,
+        };
+        assert_eq!(line(input), Ok(("", expected)));
+    }
+
+    #[test]
+    fn line_accepts_label_before_instruction() {
+        let input = "  end: HLT // done";
+        let expected = Line {
+            label: Some("end"),
+            instruction: Some(Instruction::Keyword(Keyword::Hlt)),
+        };
+        assert_eq!(line(input), Ok(("", expected)));
+    }
+
+    #[test]
+    fn pseudo_parses_register_operands() {
+        let input = "CLR b";
+        assert_eq!(
+            pseudo(input),
+            Ok(("", Instruction::Pseudo(Pseudo::Clr(GPR::B))))
+        );
+        let input = "inc a, d";
+        assert_eq!(
+            pseudo(input),
+            Ok(("", Instruction::Pseudo(Pseudo::Inc(GPR::A, GPR::D))))
+        );
+    }
+
+    #[test]
+    fn pseudo_rejects_neg_without_separate_scratch_register() {
+        let input = "neg a, a";
+        assert!(pseudo(input).is_err());
+    }
+
+    fn no_subroutines(label: &str) -> Result<(u8, u8), String> {
+        Err(format!("no subroutine {}", label))
+    }
+
+    #[test]
+    fn pseudo_expands_register_operations() {
+        assert_eq!(
+            Ok(vec![Keyword::Xor(GPR::C, GPR::C)]),
+            Pseudo::Clr(GPR::C).expand(0, &no_subroutines)
+        );
+        assert_eq!(
+            Ok(vec![Keyword::Sub(GPR::A, GPR::D)]),
+            Pseudo::Dec(GPR::A, GPR::D).expand(0, &no_subroutines)
+        );
+        assert_eq!(
+            Ok(vec![Keyword::Xor(GPR::A, GPR::D)]),
+            Pseudo::Not(GPR::A, GPR::D).expand(0, &no_subroutines)
+        );
+        assert_eq!(
+            Ok(vec![
+                Keyword::Xor(GPR::B, GPR::B),
+                Keyword::Sub(GPR::B, GPR::A),
+                Keyword::Mov(MovFrom::B, MovTo::A),
+            ]),
+            Pseudo::Neg(GPR::A, GPR::B).expand(0, &no_subroutines)
+        );
+    }
+
+    #[test]
+    fn pseudo_call_stores_return_address_into_ret() {
+        let subroutine = |label: &str| {
+            assert_eq!("print", label);
+            Ok((0x40, 0x51))
+        };
+        assert_eq!(
+            Ok(vec![
+                Keyword::Ldi(GPR::D, 0x16),
+                Keyword::St(0x51, GPR::D),
+                Keyword::Jmp(0x40),
+            ]),
+            Pseudo::Call("print", GPR::D).expand(0x10, &subroutine)
+        );
+        assert_eq!(
+            Ok(vec![Keyword::Jmp(0)]),
+            Pseudo::Ret.expand(0x51, &no_subroutines)
+        );
+    }
+
+    #[test]
+    fn pseudo_parses_call_and_ret() {
+        let input = "call print, d";
+        assert_eq!(
+            pseudo(input),
+            Ok(("", Instruction::Pseudo(Pseudo::Call("print", GPR::D))))
+        );
+        let input = "RET";
+        assert_eq!(pseudo(input), Ok(("", Instruction::Pseudo(Pseudo::Ret))));
+        let input = "call print";
+        assert!(all_consuming(pseudo)(input).is_err());
+    }
+
+    #[test]
+    fn keyword_display_parses_back() {
+        for keyword in Keyword::all().into_iter() {
+            let source = keyword.to_string();
+            let parsed = all_consuming(instruction)(&source).ok();
+            assert_eq!(
+                Some(keyword),
+                parsed.and_then(|(_, parsed)| parsed.keyword()),
+                "{}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn line_accepts_pseudo_instruction() {
+        let input = "loop: dec c, d";
+        let expected = Line {
+            label: Some("loop"),
+            instruction: Some(Instruction::Pseudo(Pseudo::Dec(GPR::C, GPR::D))),
+        };
+        assert_eq!(line(input), Ok(("", expected)));
+    }
+
+    #[test]
+    fn directive_parses_ascii_and_asciz() {
+        let input = ".ascii \"ok\"";
+        assert_eq!(
+            directive(input),
+            Ok(("", Instruction::Data(b"ok".to_vec())))
+        );
+        let input = ".ASCIZ \"ok\"";
+        assert_eq!(
+            directive(input),
+            Ok(("", Instruction::Data(b"ok\0".to_vec())))
+        );
+    }
+
+    #[test]
+    fn directive_parses_bank() {
+        let input = ".bank 2";
+        assert_eq!(directive(input), Ok(("", Instruction::Bank(2))));
+        let input = ".bank 0x10";
+        assert_eq!(directive(input), Ok(("", Instruction::Bank(0x10))));
+        let input = ".bank 0b11";
+        assert_eq!(directive(input), Ok(("", Instruction::Bank(3))));
+        let input = ".bank";
+        assert!(directive(input).is_err());
+    }
+
+    #[test]
+    fn directive_rejects_characters_and_negative_banks() {
+        assert!(all_consuming(directive)(".bank 'A'").is_err());
+        assert!(all_consuming(directive)(".bank -1").is_err());
+    }
+
+    #[test]
+    fn line_accepts_label_before_data() {
+        let input = "msg: .asciz \"; not a comment\" ; a comment";
+        let expected = Line {
+            label: Some("msg"),
+            instruction: Some(Instruction::Data(b"; not a comment\0".to_vec())),
+        };
+        assert_eq!(line(input), Ok(("", expected)));
+    }
+
+    #[test]
+    fn line_rejects_trailing_garbage() {
+        let input = "nop nop";
+        assert_eq!(line(input), Err(Err::Error(("nop", ErrorKind::Eof))));
+    }
+
+    #[test]
+    fn program_reports_failing_line() {
+        let input = "start:\n    nop\n\n    bogus\n";
+        assert_eq!(
+            program(input),
+            Err((4, Err::Error(("bogus", ErrorKind::Eof))))
+        );
+    }
+
+    #[test]
+    fn line_error_names_line_and_rest() {
+        let error = program("nop\n  mov a, x ; oops").unwrap_err();
+        assert_eq!(
+            "line 2: can't parse `mov a, x ; oops`",
+            describe_line_error(&error)
+        );
+    }
+
+    #[test]
+    fn line_error_explains_bad_literals() {
+        let error = program("ldi a, 'ä'").unwrap_err();
+        assert_eq!(
+            "line 1: only ASCII characters are allowed, found `ä`",
+            describe_line_error(&error)
+        );
+        let error = program("nop\n.ascii \"a\\qb\"").unwrap_err();
+        assert_eq!(
+            "line 2: unknown escape sequence `\\q`",
+            describe_line_error(&error)
+        );
+    }
+
+    #[test]
+    fn program_parses_every_line() {
+        let input = "; counter\nloop: add a, b\n    jmp loop\n";
+        assert_eq!(
+            program(input),
+            Ok(vec![
+                Line {
+                    label: None,
+                    instruction: None,
+                },
+                Line {
+                    label: Some("loop"),
+                    instruction: Some(Instruction::Keyword(Keyword::Add(GPR::A, GPR::B))),
+                },
+                Line {
+                    label: None,
+                    instruction: Some(Instruction::Jmp(Target::Label("loop"))),
+                },
+            ])
+        );
+    }
+
     #[test]
     fn identifier_allows_alphabetic() {
         let input = "abcdefghijklmnopqrstuvwxyz";
         assert_eq!(identifier(input), Ok(("", input)));
     }
 
+    #[test]
+    fn identifier_allows_uppercase() {
+        let input = "Loop_2: ";
+        assert_eq!(identifier(input), Ok((": ", "Loop_2")));
+        assert_eq!(label_def("Loop:"), Ok(("", "Loop")));
+    }
+
     #[test]
     fn identifier_allows__() {
         let input = "_ ";
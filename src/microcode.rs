@@ -3,6 +3,8 @@ use crate::output_datastructures::{
     PROGRAM_COUNTER, SHIFT_LEFT, SHIFT_RIGHT, SHIFT_ZERO, UNCHANGED, XOR,
 };
 
+use std::fmt;
+
 use gen_microcode::GenMicrocode;
 use gen_microcode_macro::gen_microcode;
 use field_size_macro::FieldSize;
@@ -36,8 +38,16 @@ macro_rules! ctrl_vec {
     };
 }
 
-#[derive(gen_microcode)]
-enum Keyword {
+/// The steps every instruction starts with: load the opcode at the program
+/// counter into the instruction register and advance the program counter.
+pub fn fetch_steps() -> Vec<ControlWord> {
+    let mut steps = ctrl_vec!();
+    steps.pop();
+    steps
+}
+
+#[derive(Clone, Debug, PartialEq, gen_microcode, FieldSize)]
+pub enum Keyword {
     Mov(MovFrom, MovTo),
     Sub(GPR, GPR),
     Add(GPR, GPR),
@@ -52,10 +62,135 @@ enum Keyword {
     Jz(u8),
     Hlt,
     Nop,
+    Ldi(GPR, u8),
+    Ld(GPR, u8),
+    St(u8, GPR),
+    LdInd(GPR, GPR),
 }
 
 impl Keyword {
-    fn control_words(&self) -> Vec<ControlWord> {
+    /// Every instruction with every combination of register operands, in
+    /// opcode order. Address and immediate operands are read from memory at
+    /// runtime and don't change the microcode, so they are always 0.
+    pub fn all() -> Vec<Keyword> {
+        let mut all = Vec::new();
+        for from in MOV_FROM.iter() {
+            for to in MOV_TO.iter() {
+                all.push(Keyword::Mov(*from, *to));
+            }
+        }
+        let alu: [fn(GPR, GPR) -> Keyword; 6] = [
+            Keyword::Sub,
+            Keyword::Add,
+            Keyword::And,
+            Keyword::Or,
+            Keyword::Xor,
+            Keyword::Cmp,
+        ];
+        for operation in alu.iter() {
+            for op1 in GPRS.iter() {
+                for op2 in GPRS.iter() {
+                    all.push(operation(*op1, *op2));
+                }
+            }
+        }
+        all.extend(GPRS.iter().map(|op1| Keyword::Shl(*op1)));
+        all.extend(GPRS.iter().map(|op1| Keyword::Shr(*op1)));
+        all.push(Keyword::Jmp(0));
+        all.push(Keyword::Jc(0));
+        all.push(Keyword::Jz(0));
+        all.push(Keyword::Hlt);
+        all.push(Keyword::Nop);
+        all.extend(GPRS.iter().map(|op1| Keyword::Ldi(*op1, 0)));
+        all.extend(GPRS.iter().map(|op1| Keyword::Ld(*op1, 0)));
+        all.extend(GPRS.iter().map(|op1| Keyword::St(0, *op1)));
+        for op1 in GPRS.iter() {
+            for op2 in GPRS.iter() {
+                all.push(Keyword::LdInd(*op1, *op2));
+            }
+        }
+        all
+    }
+
+    /// The opcode of the instruction: its position in `all()`. Every
+    /// combination of register operands has its own opcode, so the microcode
+    /// ROM can tell them apart. Address and immediate operands don't matter.
+    pub fn opcode(&self) -> u8 {
+        let form = match self {
+            Keyword::Jmp(_) => Keyword::Jmp(0),
+            Keyword::Jc(_) => Keyword::Jc(0),
+            Keyword::Jz(_) => Keyword::Jz(0),
+            Keyword::Ldi(r, _) => Keyword::Ldi(*r, 0),
+            Keyword::Ld(r, _) => Keyword::Ld(*r, 0),
+            Keyword::St(_, r) => Keyword::St(0, *r),
+            keyword => keyword.clone(),
+        };
+        Keyword::all().iter().position(|k| *k == form).unwrap() as u8
+    }
+
+    /// How the instruction is written, with placeholders for its operands:
+    /// `r` and `r2` are A-D, `dst` and `src` are what `mov` can write and read,
+    /// `imm` and `addr` are the byte following the opcode.
+    pub fn syntax(&self) -> &'static str {
+        match self {
+            Keyword::Mov(_, _) => "mov dst, src",
+            Keyword::Sub(_, _) => "sub r, r2",
+            Keyword::Add(_, _) => "add r, r2",
+            Keyword::And(_, _) => "and r, r2",
+            Keyword::Or(_, _) => "or r, r2",
+            Keyword::Xor(_, _) => "xor r, r2",
+            Keyword::Cmp(_, _) => "cmp r, r2",
+            Keyword::Shl(_) => "shl r",
+            Keyword::Shr(_) => "shr r",
+            Keyword::Jmp(_) => "jmp addr",
+            Keyword::Jc(_) => "jc addr",
+            Keyword::Jz(_) => "jz addr",
+            Keyword::Hlt => "hlt",
+            Keyword::Nop => "nop",
+            Keyword::Ldi(_, _) => "ldi r, imm",
+            Keyword::Ld(_, _) => "ld r, [addr]",
+            Keyword::St(_, _) => "st [addr], r",
+            Keyword::LdInd(_, _) => "ld r, [r2]",
+        }
+    }
+
+    /// The bytes of the instruction in a program: the opcode, followed by the
+    /// address or immediate if there is one.
+    pub fn encode(&self) -> Vec<u8> {
+        let opcode = self.opcode();
+        match self {
+            Keyword::Jmp(byte)
+            | Keyword::Jc(byte)
+            | Keyword::Jz(byte)
+            | Keyword::Ldi(_, byte)
+            | Keyword::Ld(_, byte)
+            | Keyword::St(byte, _) => vec![opcode, *byte],
+            _ => vec![opcode],
+        }
+    }
+
+    /// Size in bytes: the opcode plus one byte per address or immediate.
+    pub fn length(&self) -> u8 {
+        self.encode().len() as u8
+    }
+
+    /// Whether the carry and zero flags tested by `jc` and `jz` are updated.
+    /// The flags follow the ALU, so these are the instructions using it.
+    pub fn affects_flags(&self) -> bool {
+        matches!(
+            self,
+            Keyword::Sub(_, _)
+                | Keyword::Add(_, _)
+                | Keyword::And(_, _)
+                | Keyword::Or(_, _)
+                | Keyword::Xor(_, _)
+                | Keyword::Cmp(_, _)
+                | Keyword::Shl(_)
+                | Keyword::Shr(_)
+        )
+    }
+
+    pub fn control_words(&self) -> Vec<ControlWord> {
         match self {
             Keyword::Mov(from, to) => ctrl_vec!(ControlWord {
                 read_from: (*from) as u8,
@@ -144,27 +279,118 @@ impl Keyword {
                     ..ControlWord::empty()
                 }
             ),
-            Keyword::Jc(addr) => ctrl_vec!(),
-            Keyword::Jz(addr) => ctrl_vec!(),
-            Keyword::Hlt => vec![ControlWord {
+            Keyword::Jc(_) => ctrl_vec!(),
+            Keyword::Jz(_) => ctrl_vec!(),
+            Keyword::Hlt => ctrl_vec!(ControlWord {
                 halt: true,
                 ..ControlWord::empty()
-            }],
+            }),
             Keyword::Nop => ctrl_vec!(),
+            Keyword::Ldi(op1, _) => ctrl_vec!(
+                ControlWord {
+                    read_from: PROGRAM_COUNTER,
+                    write_to: MEMORY_ADDRESS,
+                    ..ControlWord::empty()
+                },
+                ControlWord {
+                    read_from: MEMORY,
+                    write_to: (*op1) as u8,
+                    program_counter_enable: true,
+                    ..ControlWord::empty()
+                }
+            ),
+            Keyword::Ld(op1, _) => ctrl_vec!(
+                ControlWord {
+                    read_from: PROGRAM_COUNTER,
+                    write_to: MEMORY_ADDRESS,
+                    ..ControlWord::empty()
+                },
+                ControlWord {
+                    read_from: MEMORY,
+                    write_to: MEMORY_ADDRESS,
+                    program_counter_enable: true,
+                    ..ControlWord::empty()
+                },
+                ControlWord {
+                    read_from: MEMORY,
+                    write_to: (*op1) as u8,
+                    ..ControlWord::empty()
+                }
+            ),
+            Keyword::St(_, op1) => ctrl_vec!(
+                ControlWord {
+                    read_from: PROGRAM_COUNTER,
+                    write_to: MEMORY_ADDRESS,
+                    ..ControlWord::empty()
+                },
+                ControlWord {
+                    read_from: MEMORY,
+                    write_to: MEMORY_ADDRESS,
+                    program_counter_enable: true,
+                    ..ControlWord::empty()
+                },
+                ControlWord {
+                    read_from: (*op1) as u8,
+                    write_to: MEMORY,
+                    ..ControlWord::empty()
+                }
+            ),
+            Keyword::LdInd(op1, op2) => ctrl_vec!(
+                ControlWord {
+                    read_from: (*op2) as u8,
+                    write_to: MEMORY_ADDRESS,
+                    ..ControlWord::empty()
+                },
+                ControlWord {
+                    read_from: MEMORY,
+                    write_to: (*op1) as u8,
+                    ..ControlWord::empty()
+                }
+            ),
         }
     }
 }
 
-#[derive(Copy, Clone, FieldSize)]
-enum GPR {
+/// The instruction as it is written in a program, e.g. `mov a, b` or
+/// `ld c, [0x80]`.
+impl fmt::Display for Keyword {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = |operand: &dyn fmt::Debug| format!("{:?}", operand).to_lowercase();
+        match self {
+            Keyword::Mov(from, to) => write!(f, "mov {}, {}", name(to), name(from)),
+            Keyword::Sub(r, r2) => write!(f, "sub {}, {}", name(r), name(r2)),
+            Keyword::Add(r, r2) => write!(f, "add {}, {}", name(r), name(r2)),
+            Keyword::And(r, r2) => write!(f, "and {}, {}", name(r), name(r2)),
+            Keyword::Or(r, r2) => write!(f, "or {}, {}", name(r), name(r2)),
+            Keyword::Xor(r, r2) => write!(f, "xor {}, {}", name(r), name(r2)),
+            Keyword::Cmp(r, r2) => write!(f, "cmp {}, {}", name(r), name(r2)),
+            Keyword::Shl(r) => write!(f, "shl {}", name(r)),
+            Keyword::Shr(r) => write!(f, "shr {}", name(r)),
+            Keyword::Jmp(addr) => write!(f, "jmp 0x{:02X}", addr),
+            Keyword::Jc(addr) => write!(f, "jc 0x{:02X}", addr),
+            Keyword::Jz(addr) => write!(f, "jz 0x{:02X}", addr),
+            Keyword::Hlt => write!(f, "hlt"),
+            Keyword::Nop => write!(f, "nop"),
+            Keyword::Ldi(r, imm) => write!(f, "ldi {}, 0x{:02X}", name(r), imm),
+            Keyword::Ld(r, addr) => write!(f, "ld {}, [0x{:02X}]", name(r), addr),
+            Keyword::St(addr, r) => write!(f, "st [0x{:02X}], {}", addr, name(r)),
+            Keyword::LdInd(r, r2) => write!(f, "ld {}, [{}]", name(r), name(r2)),
+        }
+    }
+}
+
+/// General purpose registers.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Copy, Clone, Debug, PartialEq, FieldSize)]
+pub enum GPR {
     A = 0,
     B,
     C,
     D,
 }
 
-#[derive(Copy, Clone, FieldSize)]
-enum MovFrom {
+#[derive(Copy, Clone, Debug, PartialEq, FieldSize)]
+pub enum MovFrom {
     A = 0,
     B = 1,
     C = 2,
@@ -173,8 +399,8 @@ enum MovFrom {
     Acc = 6,
 }
 
-#[derive(Copy, Clone, FieldSize)]
-enum MovTo {
+#[derive(Copy, Clone, Debug, PartialEq, FieldSize)]
+pub enum MovTo {
     A = 0,
     B = 1,
     C = 2,
@@ -183,11 +409,136 @@ enum MovTo {
     Out = 8,
 }
 
-fn generate() {}
+const GPRS: [GPR; 4] = [GPR::A, GPR::B, GPR::C, GPR::D];
+
+const MOV_FROM: [MovFrom; 6] = [
+    MovFrom::A,
+    MovFrom::B,
+    MovFrom::C,
+    MovFrom::D,
+    MovFrom::BS,
+    MovFrom::Acc,
+];
+
+const MOV_TO: [MovTo; 6] = [
+    MovTo::A,
+    MovTo::B,
+    MovTo::C,
+    MovTo::D,
+    MovTo::BS,
+    MovTo::Out,
+];
+
+impl From<GPR> for MovFrom {
+    fn from(register: GPR) -> Self {
+        match register {
+            GPR::A => MovFrom::A,
+            GPR::B => MovFrom::B,
+            GPR::C => MovFrom::C,
+            GPR::D => MovFrom::D,
+        }
+    }
+}
+
+impl From<GPR> for MovTo {
+    fn from(register: GPR) -> Self {
+        match register {
+            GPR::A => MovTo::A,
+            GPR::B => MovTo::B,
+            GPR::C => MovTo::C,
+            GPR::D => MovTo::D,
+        }
+    }
+}
+
+pub struct MicrocodeConfig {
+    /// Width of the hardware step counter. No instruction may take more than
+    /// 2^step_counter_bits steps.
+    pub step_counter_bits: u32,
+    /// Leave out the final step_reset if an instruction fills the step counter
+    /// exactly, and let the counter wrap around to 0 instead.
+    pub wrap_around: bool,
+}
+
+impl Default for MicrocodeConfig {
+    fn default() -> Self {
+        MicrocodeConfig {
+            step_counter_bits: 3,
+            wrap_around: false,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct TooManySteps {
+    pub keyword: Keyword,
+    pub steps: usize,
+    pub step_counter_bits: u32,
+}
+
+impl fmt::Display for TooManySteps {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} takes {} steps, but a {} bit step counter has room for {} steps",
+            self.keyword,
+            self.steps,
+            self.step_counter_bits,
+            step_capacity(self.step_counter_bits)
+        )
+    }
+}
+
+/// Number of steps a counter of the given width has room for. Anything wider
+/// than a `usize` is as good as unlimited.
+pub fn step_capacity(step_counter_bits: u32) -> usize {
+    1usize.checked_shl(step_counter_bits).unwrap_or(usize::MAX)
+}
+
+/// The microcode of every instruction, checked against the step counter.
+pub fn generate(
+    config: &MicrocodeConfig,
+) -> Result<Vec<(Keyword, Vec<ControlWord>)>, TooManySteps> {
+    Keyword::all()
+        .into_iter()
+        .map(|keyword| {
+            let steps = fit_step_counter(&keyword, config)?;
+            Ok((keyword, steps))
+        })
+        .collect()
+}
+
+/// The steps of `keyword` as the step counter of `config` runs through them.
+pub fn fit_step_counter(
+    keyword: &Keyword,
+    config: &MicrocodeConfig,
+) -> Result<Vec<ControlWord>, TooManySteps> {
+    let capacity = step_capacity(config.step_counter_bits);
+    let bare_reset = ControlWord {
+        step_reset: true,
+        ..ControlWord::empty()
+    };
+    let mut steps = keyword.control_words();
+    if config.wrap_around
+        && steps.len() == capacity.saturating_add(1)
+        && steps.last() == Some(&bare_reset)
+    {
+        steps.pop();
+    }
+    if steps.len() > capacity {
+        return Err(TooManySteps {
+            keyword: keyword.clone(),
+            steps: steps.len(),
+            step_counter_bits: config.step_counter_bits,
+        });
+    }
+    Ok(steps)
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::output_datastructures::{REGISTER_A, REGISTER_C};
 
     fn fetch_cycle() -> Vec<ControlWord> {
         vec![
@@ -228,4 +579,234 @@ mod tests {
     fn test() {
         Keyword::test();
     }
+
+    #[test]
+    fn ldi_reads_operand_and_skips_it() {
+        let words = Keyword::Ldi(GPR::C, 42).control_words();
+        assert_eq!(5, words.len());
+        assert_eq!(
+            ControlWord {
+                read_from: PROGRAM_COUNTER,
+                write_to: MEMORY_ADDRESS,
+                ..ControlWord::empty()
+            },
+            words[2]
+        );
+        assert_eq!(
+            ControlWord {
+                read_from: MEMORY,
+                write_to: REGISTER_C,
+                program_counter_enable: true,
+                ..ControlWord::empty()
+            },
+            words[3]
+        );
+    }
+
+    #[test]
+    fn ld_loads_address_operand_into_memory_address() {
+        let words = Keyword::Ld(GPR::C, 0x80).control_words();
+        assert_eq!(6, words.len());
+        assert_eq!(
+            ControlWord {
+                read_from: MEMORY,
+                write_to: MEMORY_ADDRESS,
+                program_counter_enable: true,
+                ..ControlWord::empty()
+            },
+            words[3]
+        );
+        assert_eq!(
+            ControlWord {
+                read_from: MEMORY,
+                write_to: REGISTER_C,
+                ..ControlWord::empty()
+            },
+            words[4]
+        );
+    }
+
+    #[test]
+    fn st_writes_register_to_memory() {
+        let words = Keyword::St(0x80, GPR::C).control_words();
+        assert_eq!(6, words.len());
+        assert_eq!(
+            ControlWord {
+                read_from: REGISTER_C,
+                write_to: MEMORY,
+                ..ControlWord::empty()
+            },
+            words[4]
+        );
+    }
+
+    #[test]
+    fn ld_ind_uses_register_as_address() {
+        let words = Keyword::LdInd(GPR::A, GPR::C).control_words();
+        assert_eq!(5, words.len());
+        assert_eq!(
+            ControlWord {
+                read_from: REGISTER_C,
+                write_to: MEMORY_ADDRESS,
+                ..ControlWord::empty()
+            },
+            words[2]
+        );
+        assert_eq!(
+            ControlWord {
+                read_from: MEMORY,
+                write_to: REGISTER_A,
+                ..ControlWord::empty()
+            },
+            words[3]
+        );
+    }
+
+    #[test]
+    fn fetch_steps_are_fetch_cycle_without_step_reset() {
+        let mut expected = fetch_cycle();
+        expected.pop();
+        assert_eq!(expected, fetch_steps());
+    }
+
+    #[test]
+    fn all_covers_every_instruction_and_register_combination() {
+        let all = Keyword::all();
+        let mut instructions: Vec<u8> = all.iter().cloned().map(u8::from).collect();
+        instructions.dedup();
+        assert_eq!(Keyword::field_size(), instructions.len());
+        assert_eq!(36 + 7 * 16 + 5 * 4 + 5, all.len());
+    }
+
+    #[test]
+    fn hlt_halts_after_fetch() {
+        let words = Keyword::Hlt.control_words();
+        assert_eq!(4, words.len());
+        assert!(words[2].halt);
+    }
+
+    #[test]
+    fn generate_keeps_sequences_that_fit() {
+        let microcode = generate(&MicrocodeConfig::default()).unwrap();
+        assert_eq!(Keyword::all().len(), microcode.len());
+        let (keyword, steps) = &microcode[0];
+        assert_eq!(&keyword.control_words(), steps);
+    }
+
+    #[test]
+    fn generate_names_instruction_with_too_many_steps() {
+        let config = MicrocodeConfig {
+            step_counter_bits: 2,
+            wrap_around: false,
+        };
+        assert_eq!(
+            Err(TooManySteps {
+                keyword: Keyword::Jmp(0),
+                steps: 5,
+                step_counter_bits: 2,
+            }),
+            generate(&config)
+        );
+    }
+
+    #[test]
+    fn too_many_steps_is_readable() {
+        let error = TooManySteps {
+            keyword: Keyword::Ld(GPR::A, 0),
+            steps: 6,
+            step_counter_bits: 2,
+        };
+        assert_eq!(
+            "Ld(A, 0) takes 6 steps, but a 2 bit step counter has room for 4 steps",
+            error.to_string()
+        );
+    }
+
+    #[test]
+    fn wide_step_counters_do_not_overflow() {
+        let config = MicrocodeConfig {
+            step_counter_bits: 200,
+            wrap_around: true,
+        };
+        assert!(generate(&config).is_ok());
+        let error = TooManySteps {
+            keyword: Keyword::Nop,
+            steps: 3,
+            step_counter_bits: 64,
+        };
+        let expected = format!("{} steps", usize::MAX);
+        assert!(error.to_string().ends_with(&expected));
+    }
+
+    #[test]
+    fn fit_step_counter_wraps_around_when_counter_is_exactly_full() {
+        let config = MicrocodeConfig {
+            step_counter_bits: 2,
+            wrap_around: true,
+        };
+        let steps = fit_step_counter(&Keyword::Jmp(0), &config).unwrap();
+        assert_eq!(4, steps.len());
+        assert!(!steps[3].step_reset);
+
+        let steps = fit_step_counter(&Keyword::Nop, &config).unwrap();
+        assert_eq!(fetch_cycle(), steps);
+
+        let error = fit_step_counter(&Keyword::Ld(GPR::A, 0), &config).unwrap_err();
+        assert_eq!(6, error.steps);
+    }
+
+    #[test]
+    fn fit_step_counter_keeps_step_reset_without_wrap_around() {
+        let config = MicrocodeConfig {
+            step_counter_bits: 2,
+            wrap_around: false,
+        };
+        let error = fit_step_counter(&Keyword::Jmp(0), &config).unwrap_err();
+        assert_eq!(5, error.steps);
+    }
+
+    #[test]
+    fn opcode_tells_register_operands_apart() {
+        assert_eq!(0x00, Keyword::Mov(MovFrom::A, MovTo::A).opcode());
+        assert_eq!(0x23, Keyword::Mov(MovFrom::Acc, MovTo::Out).opcode());
+        assert_eq!(0x35, Keyword::Add(GPR::A, GPR::B).opcode());
+        assert_eq!(0x43, Keyword::Add(GPR::D, GPR::D).opcode());
+        assert_eq!(0x8C, Keyword::Jmp(0xFF).opcode());
+        assert_eq!(0x8F, Keyword::Hlt.opcode());
+        assert_eq!(0x91, Keyword::Ldi(GPR::A, 1).opcode());
+        assert_eq!(0xAC, Keyword::LdInd(GPR::D, GPR::D).opcode());
+    }
+
+    #[test]
+    fn opcodes_are_unique_and_fit_into_a_byte() {
+        let opcodes: Vec<u8> = Keyword::all().iter().map(Keyword::opcode).collect();
+        let expected: Vec<u8> = (0..Keyword::all().len() as u8).collect();
+        assert_eq!(expected, opcodes);
+    }
+
+    #[test]
+    fn encode_appends_address_or_immediate() {
+        assert_eq!(vec![0x06], Keyword::Mov(MovFrom::B, MovTo::A).encode());
+        assert_eq!(vec![0x93, 0x2A], Keyword::Ldi(GPR::C, 0x2A).encode());
+        assert_eq!(vec![0x99, 0x80], Keyword::St(0x80, GPR::A).encode());
+    }
+
+    #[test]
+    fn display_writes_source_syntax() {
+        assert_eq!(
+            "mov a, acc",
+            Keyword::Mov(MovFrom::Acc, MovTo::A).to_string()
+        );
+        assert_eq!("xor b, b", Keyword::Xor(GPR::B, GPR::B).to_string());
+        assert_eq!("st [0x80], d", Keyword::St(0x80, GPR::D).to_string());
+        assert_eq!("ld a, [c]", Keyword::LdInd(GPR::A, GPR::C).to_string());
+    }
+
+    #[test]
+    fn length_counts_operand_bytes() {
+        assert_eq!(1, Keyword::Add(GPR::A, GPR::B).length());
+        assert_eq!(1, Keyword::LdInd(GPR::A, GPR::B).length());
+        assert_eq!(2, Keyword::Ldi(GPR::A, 1).length());
+        assert_eq!(2, Keyword::St(0x10, GPR::A).length());
+    }
 }
@@ -10,6 +10,95 @@ pub const MEMORY: u8 = 7;
 pub const OUTPUT: u8 = 8;
 pub const INSTRUCTION: u8 = 9;
 
+/// A register or other bus participant addressed by `read_from`/`write_to`.
+pub struct Endpoint {
+    pub name: &'static str,
+    pub short_name: &'static str,
+    pub code: u8,
+    pub readable: bool,
+    pub writable: bool,
+}
+
+pub const ENDPOINTS: [Endpoint; 11] = [
+    Endpoint {
+        name: "REGISTER_A",
+        short_name: "A",
+        code: REGISTER_A,
+        readable: true,
+        writable: true,
+    },
+    Endpoint {
+        name: "REGISTER_B",
+        short_name: "B",
+        code: REGISTER_B,
+        readable: true,
+        writable: true,
+    },
+    Endpoint {
+        name: "REGISTER_C",
+        short_name: "C",
+        code: REGISTER_C,
+        readable: true,
+        writable: true,
+    },
+    Endpoint {
+        name: "REGISTER_D",
+        short_name: "D",
+        code: REGISTER_D,
+        readable: true,
+        writable: true,
+    },
+    Endpoint {
+        name: "PROGRAM_COUNTER",
+        short_name: "PC",
+        code: PROGRAM_COUNTER,
+        readable: true,
+        writable: true,
+    },
+    Endpoint {
+        name: "BANK_SELECT",
+        short_name: "BS",
+        code: BANK_SELECT,
+        readable: true,
+        writable: true,
+    },
+    Endpoint {
+        name: "ACCUMULATOR",
+        short_name: "ACC",
+        code: ACCUMULATOR,
+        readable: true,
+        writable: false,
+    },
+    Endpoint {
+        name: "MEMORY_ADDRESS",
+        short_name: "MAR",
+        code: MEMORY_ADDRESS,
+        readable: false,
+        writable: true,
+    },
+    Endpoint {
+        name: "MEMORY",
+        short_name: "RAM",
+        code: MEMORY,
+        readable: true,
+        writable: true,
+    },
+    Endpoint {
+        name: "OUTPUT",
+        short_name: "OUT",
+        code: OUTPUT,
+        readable: false,
+        writable: true,
+    },
+    Endpoint {
+        name: "INSTRUCTION",
+        short_name: "IR",
+        code: INSTRUCTION,
+        readable: false,
+        writable: true,
+    },
+];
+
 // TODO: choose right values
 pub const SHIFT_ZERO: u8 = 0;
 pub const SHIFT_LEFT: u8 = 1;
@@ -34,6 +123,61 @@ pub const A_OR_NOT_B: u8 = 0;
 pub const B_OR_NOT_A: u8 = 0;
 pub const ONES: u8 = 15;
 
+const LOGIC_FUNCTIONS: [(&str, u8); 16] = [
+    ("ZERO", LOGIC_ZERO),
+    ("AND", AND),
+    ("OR", OR),
+    ("XOR", XOR),
+    ("NAND", NAND),
+    ("NOR", NOR),
+    ("XNOR", XNOR),
+    ("A", LOGIC_A),
+    ("B", LOGIC_B),
+    ("NOT_A", NOT_A),
+    ("NOT_B", NOT_B),
+    ("A_AND_NOT_B", A_AND_NOT_B),
+    ("B_AND_NOT_A", B_AND_NOT_A),
+    ("A_OR_NOT_B", A_OR_NOT_B),
+    ("B_OR_NOT_A", B_OR_NOT_A),
+    ("ONES", ONES),
+];
+
+/// Name and width in bits of every `ControlWord` field, in the order they are
+/// packed into the EEPROMs.
+pub const FIELDS: [(&str, usize); 11] = [
+    ("write_to", 4),
+    ("read_from", 3),
+    ("alu_left", 2),
+    ("alu_right", 2),
+    ("alu_shift", 2),
+    ("alu_logic", 4),
+    ("alu_subtract", 1),
+    ("program_counter_enable", 1),
+    ("bank_select_enable", 1),
+    ("halt", 1),
+    ("step_reset", 1),
+];
+
+fn shift_name(shift: u8) -> String {
+    match shift {
+        SHIFT_ZERO => String::from("ZERO"),
+        SHIFT_LEFT => String::from("LEFT"),
+        SHIFT_RIGHT => String::from("RIGHT"),
+        UNCHANGED => String::from("UNCHANGED"),
+        _ => format!("#{}", shift),
+    }
+}
+
+/// Names `logic` if exactly one logic function has that value. While most
+/// of them are still placeholders sharing 0, those print as `#0`.
+fn logic_name(logic: u8) -> String {
+    let mut names = LOGIC_FUNCTIONS.iter().filter(|(_, value)| *value == logic);
+    match (names.next(), names.next()) {
+        (Some((name, _)), None) => String::from(*name),
+        _ => format!("#{}", logic),
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub struct ControlWord {
     pub write_to: u8,
@@ -66,6 +210,102 @@ impl ControlWord {
         }
     }
 
+    /// The value of every field, in the order of `FIELDS`.
+    pub fn field_values(&self) -> [u8; 11] {
+        [
+            self.write_to,
+            self.read_from,
+            self.alu_left,
+            self.alu_right,
+            self.alu_shift,
+            self.alu_logic,
+            self.alu_subtract as u8,
+            self.program_counter_enable as u8,
+            self.bank_select_enable as u8,
+            self.halt as u8,
+            self.step_reset as u8,
+        ]
+    }
+
+    /// Names of the signals doing something in this step, e.g. `PC→MAR` or
+    /// `PC+`. The bus has no idle code, so the transfer of an empty control
+    /// word, A→A, is not listed. The ALU is listed when its result is read or
+    /// any of its inputs is set.
+    pub fn signals(&self) -> Vec<String> {
+        let endpoint = |code: u8, readable: bool| {
+            ENDPOINTS
+                .iter()
+                .find(|e| e.code == code && if readable { e.readable } else { e.writable })
+                .map(|e| String::from(e.short_name))
+                .unwrap_or_else(|| format!("#{}", code))
+        };
+        let empty = ControlWord::empty();
+        let mut signals = Vec::new();
+        if (self.read_from, self.write_to) != (empty.read_from, empty.write_to) {
+            signals.push(format!(
+                "{}→{}",
+                endpoint(self.read_from, true),
+                endpoint(self.write_to, false)
+            ));
+        }
+        if self.read_from == ACCUMULATOR
+            || self.alu_left != empty.alu_left
+            || self.alu_right != empty.alu_right
+            || self.alu_shift != empty.alu_shift
+            || self.alu_logic != empty.alu_logic
+            || self.alu_subtract
+        {
+            signals.push(format!(
+                "ALU {}{}{} shift={} logic={}",
+                endpoint(self.alu_left, true),
+                if self.alu_subtract { "-" } else { "," },
+                endpoint(self.alu_right, true),
+                shift_name(self.alu_shift),
+                logic_name(self.alu_logic)
+            ));
+        }
+        if self.program_counter_enable {
+            signals.push(String::from("PC+"));
+        }
+        if self.bank_select_enable {
+            signals.push(String::from("BANK"));
+        }
+        if self.halt {
+            signals.push(String::from("HLT"));
+        }
+        if self.step_reset {
+            signals.push(String::from("STEP=0"));
+        }
+        signals
+    }
+
+    /// The bytes burned into the three EEPROMs, most significant first.
+    pub fn to_bytes(&self) -> [u8; 3] {
+        [
+            self.most_significant_bits(),
+            self.middle_bits(),
+            self.least_significant_bits(),
+        ]
+    }
+
+    /// Reads back a control word dumped with `to_bytes`.
+    pub fn from_bytes(bytes: [u8; 3]) -> ControlWord {
+        let [msb, middle, lsb] = bytes;
+        ControlWord {
+            write_to: msb >> 4,
+            read_from: msb >> 1 & 0b111,
+            alu_left: (msb & 1) << 1 | middle >> 7,
+            alu_right: middle >> 5 & 0b11,
+            alu_shift: middle >> 3 & 0b11,
+            alu_logic: (middle & 0b111) << 1 | lsb >> 7,
+            alu_subtract: lsb & 1 << 6 != 0,
+            program_counter_enable: lsb & 1 << 5 != 0,
+            bank_select_enable: lsb & 1 << 4 != 0,
+            halt: lsb & 1 << 3 != 0,
+            step_reset: lsb & 1 << 2 != 0,
+        }
+    }
+
     fn most_significant_bits(&self) -> u8 {
         self.write_to << 4 | self.read_from << 1 | self.alu_left >> 1
     }
@@ -103,6 +343,129 @@ mod tests {
         }
     }
 
+    #[test]
+    fn fields_fill_three_bytes_with_two_bits_to_spare() {
+        let width: usize = FIELDS.iter().map(|(_, width)| width).sum();
+        assert_eq!(22, width);
+    }
+
+    #[test]
+    fn field_values_follow_fields_order() {
+        let control_word = ControlWord {
+            write_to: MEMORY,
+            read_from: REGISTER_D,
+            alu_logic: ONES,
+            halt: true,
+            ..standard_control_word()
+        };
+
+        assert_eq!(
+            [7, 3, 0, 0, 0, 15, 0, 0, 0, 1, 0],
+            control_word.field_values()
+        );
+    }
+
+    #[test]
+    fn signals_name_bus_transfer_by_direction() {
+        let control_word = ControlWord {
+            read_from: PROGRAM_COUNTER,
+            write_to: MEMORY_ADDRESS,
+            ..standard_control_word()
+        };
+
+        assert_eq!(vec!["PC→MAR"], control_word.signals());
+    }
+
+    #[test]
+    fn signals_name_flags() {
+        let control_word = ControlWord {
+            read_from: MEMORY,
+            write_to: INSTRUCTION,
+            program_counter_enable: true,
+            halt: true,
+            step_reset: true,
+            ..standard_control_word()
+        };
+
+        assert_eq!(
+            vec!["RAM→IR", "PC+", "HLT", "STEP=0"],
+            control_word.signals()
+        );
+    }
+
+    #[test]
+    fn signals_name_alu_when_result_is_read() {
+        let control_word = ControlWord {
+            read_from: ACCUMULATOR,
+            write_to: REGISTER_B,
+            alu_left: REGISTER_B,
+            alu_right: REGISTER_C,
+            alu_subtract: true,
+            alu_shift: UNCHANGED,
+            ..standard_control_word()
+        };
+
+        assert_eq!(
+            vec!["ACC→B", "ALU B-C shift=UNCHANGED logic=#0"],
+            control_word.signals()
+        );
+    }
+
+    #[test]
+    fn signals_skip_idle_bus() {
+        assert_eq!(Vec::<String>::new(), ControlWord::empty().signals());
+    }
+
+    #[test]
+    fn signals_tell_endpoints_sharing_a_code_apart() {
+        let control_word = ControlWord {
+            read_from: ACCUMULATOR,
+            write_to: MEMORY_ADDRESS,
+            ..ControlWord::empty()
+        };
+        assert_eq!("ACC→MAR", control_word.signals()[0]);
+
+        let control_word = ControlWord {
+            read_from: MEMORY,
+            write_to: MEMORY,
+            ..ControlWord::empty()
+        };
+        assert_eq!(vec!["RAM→RAM"], control_word.signals());
+    }
+
+    #[test]
+    fn logic_is_named_unless_ambiguous() {
+        assert_eq!("ONES", logic_name(ONES));
+        assert_eq!("#0", logic_name(LOGIC_ZERO));
+        assert_eq!("#7", logic_name(7));
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let control_word = ControlWord {
+            write_to: OUTPUT,
+            read_from: MEMORY,
+            alu_left: REGISTER_D,
+            alu_right: REGISTER_C,
+            alu_shift: UNCHANGED,
+            alu_logic: 0b1011,
+            alu_subtract: true,
+            program_counter_enable: false,
+            bank_select_enable: true,
+            halt: false,
+            step_reset: true,
+        };
+
+        assert_eq!(
+            control_word,
+            ControlWord::from_bytes(control_word.to_bytes())
+        );
+        assert_eq!(
+            ControlWord::empty(),
+            ControlWord::from_bytes(ControlWord::empty().to_bytes())
+        );
+    }
+
     #[test]
     fn msb_conversion_uses_write_to() {
         let control_word = ControlWord {
@@ -0,0 +1,122 @@
+use crate::microcode::step_capacity;
+use crate::output_datastructures::{ControlWord, FIELDS};
+use std::fmt::Write;
+
+/// Renders the control signals of consecutive microcode steps as a Value
+/// Change Dump. Every step is one clock period: the step counter and the
+/// control word change on the falling edge at its start, registers latch on
+/// the rising edge in its middle. The step counter is `step_counter_bits` wide
+/// and follows `step_reset` the way the hardware counter does, wrapping around
+/// to 0 when it runs past its last step.
+pub fn write_vcd(steps: &[ControlWord], step_counter_bits: u32) -> String {
+    let capacity = step_capacity(step_counter_bits);
+    let mut signals = vec![("clock", 1), ("step", step_counter_bits as usize)];
+    signals.extend(FIELDS.iter().cloned());
+
+    let mut vcd = String::new();
+    writeln!(vcd, "$timescale 1us $end").unwrap();
+    writeln!(vcd, "$scope module cpu $end").unwrap();
+    for (index, (name, width)) in signals.iter().enumerate() {
+        writeln!(
+            vcd,
+            "$var wire {} {} {} $end",
+            width,
+            identifier(index),
+            name
+        )
+        .unwrap();
+    }
+    writeln!(vcd, "$upscope $end").unwrap();
+    writeln!(vcd, "$enddefinitions $end").unwrap();
+
+    let mut previous: Option<Vec<u8>> = None;
+    let mut step = 0;
+    for (cycle, control_word) in steps.iter().enumerate() {
+        let mut values = vec![0, step as u8];
+        values.extend(control_word.field_values().iter());
+
+        writeln!(vcd, "#{}", 2 * cycle).unwrap();
+        for (index, value) in values.iter().enumerate() {
+            if previous.as_ref().map(|p| p[index]) != Some(*value) {
+                let width = signals[index].1;
+                writeln!(vcd, "{}", value_change(*value, width, index)).unwrap();
+            }
+        }
+        writeln!(vcd, "#{}", 2 * cycle + 1).unwrap();
+        writeln!(vcd, "{}", value_change(1, 1, 0)).unwrap();
+        values[0] = 1;
+
+        step = if control_word.step_reset {
+            0
+        } else {
+            (step + 1) % capacity
+        };
+        previous = Some(values);
+    }
+    writeln!(vcd, "#{}", 2 * steps.len()).unwrap();
+    vcd
+}
+
+/// VCD identifiers are short strings of printable characters.
+fn identifier(index: usize) -> char {
+    (b'!' + index as u8) as char
+}
+
+fn value_change(value: u8, width: usize, index: usize) -> String {
+    if width == 1 {
+        format!("{}{}", value, identifier(index))
+    } else {
+        format!("b{:b} {}", value, identifier(index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output_datastructures::{MEMORY, PROGRAM_COUNTER};
+
+    #[test]
+    fn header_declares_clock_step_and_every_field() {
+        let vcd = write_vcd(&[ControlWord::empty()], 3);
+        assert!(vcd.contains("$var wire 1 ! clock $end"));
+        assert!(vcd.contains("$var wire 3 \" step $end"));
+        assert!(vcd.contains("$var wire 4 # write_to $end"));
+        assert!(vcd.contains("$var wire 1 - step_reset $end"));
+        assert!(vcd.contains("$enddefinitions $end"));
+    }
+
+    #[test]
+    fn dumps_only_changed_values() {
+        let steps = [
+            ControlWord {
+                read_from: PROGRAM_COUNTER,
+                ..ControlWord::empty()
+            },
+            ControlWord {
+                read_from: MEMORY,
+                step_reset: true,
+                ..ControlWord::empty()
+            },
+            ControlWord::empty(),
+        ];
+        let vcd = write_vcd(&steps, 2);
+        let body = &vcd[vcd.find("#0").unwrap()..];
+        let expected = [
+            "#0", "0!", "b0 \"", "b0 #", "b100 $", "b0 %", "b0 &", "b0 '", "b0 (", "0)", "0*",
+            "0+", "0,", "0-", "#1", "1!", //
+            "#2", "0!", "b1 \"", "b111 $", "1-", "#3", "1!", //
+            "#4", "0!", "b0 \"", "b0 $", "0-", "#5", "1!", //
+            "#6", "",
+        ]
+        .join("\n");
+        assert_eq!(expected, body);
+    }
+
+    #[test]
+    fn step_counter_wraps_around_without_step_reset() {
+        let steps: Vec<ControlWord> = (0..5).map(|_| ControlWord::empty()).collect();
+        let vcd = write_vcd(&steps, 2);
+        let steps: Vec<&str> = vcd.lines().filter(|line| line.ends_with(" \"")).collect();
+        assert_eq!(vec!["b0 \"", "b1 \"", "b10 \"", "b11 \"", "b0 \""], steps);
+    }
+}